@@ -0,0 +1,80 @@
+use std::hint::black_box;
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use game_core::{BitBoard, Board, Piece, PieceMask, RotationSystem, Srs, Tetromino};
+
+const W: i32 = 10;
+const H: i32 = 20;
+const HIDDEN: i32 = 20;
+
+// A ragged stack with four full rows at the bottom.
+fn stack() -> Board {
+    let mut board = Board::new(W, H, HIDDEN);
+    for y in 8..H {
+        for x in 0..W {
+            if y >= H - 4 || (x * 7 + y * 3) % 5 != 0 {
+                board.set(x, y, 1 + (x % 7) as u8);
+            }
+        }
+    }
+    board
+}
+
+// Every placement an AI would try: all kinds, rotations and columns, at
+// every height.
+fn collision(c: &mut Criterion) {
+    let board = stack();
+    let bits = BitBoard::from(&board);
+    let occupied = |x: i32, y: i32| !board.in_bounds(x, y) || board.get(x, y) != 0;
+    let masks: Vec<_> = Tetromino::ALL
+        .iter()
+        .flat_map(|&kind| (0..4).map(move |rot| (kind, rot, PieceMask::of(&Srs, kind, rot))))
+        .collect();
+
+    let mut group = c.benchmark_group("collision");
+    group.bench_function("board", |b| {
+        b.iter(|| {
+            let mut fits = 0;
+            for &(kind, rot, _) in &masks {
+                for x in -2..W {
+                    for y in -2..H {
+                        let piece = Piece { kind, rot, x, y };
+                        fits += Srs.fits(black_box(piece), &occupied) as u32;
+                    }
+                }
+            }
+            fits
+        })
+    });
+    group.bench_function("bitboard", |b| {
+        b.iter(|| {
+            let mut fits = 0;
+            for (_, _, mask) in &masks {
+                for x in -2..W {
+                    for y in -2..H {
+                        fits += bits.fits(black_box(mask), x, y) as u32;
+                    }
+                }
+            }
+            fits
+        })
+    });
+    group.finish();
+}
+
+fn clear_lines(c: &mut Criterion) {
+    let board = stack();
+    let bits = BitBoard::from(&board);
+
+    let mut group = c.benchmark_group("clear_lines");
+    group.bench_function("board", |b| {
+        b.iter_batched_ref(|| board.clone(), |b| b.clear_lines(), BatchSize::SmallInput)
+    });
+    group.bench_function("bitboard", |b| {
+        b.iter_batched_ref(|| bits.clone(), |b| b.clear_lines(), BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, collision, clear_lines);
+criterion_main!(benches);
@@ -0,0 +1,268 @@
+use crate::{Board, Cell, RotationSystem, Tetromino};
+
+/// Widest board a [`BitBoard`] can hold: one bit per column in a `u64`.
+pub const MAX_WIDTH: i32 = 64;
+
+/// The playfield as one occupancy bitmask per row, bit `x` for column `x`,
+/// with the cell colors kept alongside for rendering.
+///
+/// Checking a row for fullness is a single compare and collision tests a
+/// whole piece row at a time, which is what searches and bulk simulations
+/// spend their time on. Coordinates match [`Board`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BitBoard {
+    width: i32,
+    height: i32,
+    hidden: i32,
+    // Bits of a full row.
+    full: u64,
+    rows: Vec<u64>,
+    colors: Vec<Cell>,
+}
+
+/// A piece shape as row bitmasks, anchored at its leftmost column and
+/// topmost row. Build one per kind and rotation and reuse it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PieceMask {
+    rows: [u64; 4],
+    width: i32,
+    height: usize,
+    // Offset of the mask's top left corner from the piece origin.
+    left: i32,
+    top: i32,
+}
+
+impl PieceMask {
+    /// The mask of a shape given as block offsets from the piece origin.
+    pub fn new(shape: [(i8, i8); 4]) -> Self {
+        let left = shape.iter().map(|b| b.0).min().unwrap_or(0);
+        let top = shape.iter().map(|b| b.1).min().unwrap_or(0);
+        let mut rows = [0; 4];
+        let (mut width, mut height) = (0, 0);
+        for (dx, dy) in shape {
+            let row = (dy - top) as usize;
+            rows[row] |= 1 << (dx - left);
+            width = width.max((dx - left) as i32 + 1);
+            height = height.max(row + 1);
+        }
+        PieceMask {
+            rows,
+            width,
+            height,
+            left: left as i32,
+            top: top as i32,
+        }
+    }
+
+    /// The mask of `kind` in rotation state `rot` under `rotation`.
+    pub fn of(rotation: &dyn RotationSystem, kind: Tetromino, rot: u8) -> Self {
+        Self::new(rotation.shape(kind, rot))
+    }
+}
+
+impl BitBoard {
+    /// An empty board. Panics if `width` is over [`MAX_WIDTH`].
+    pub fn new(width: i32, height: i32, hidden: i32) -> Self {
+        assert!(
+            (1..=MAX_WIDTH).contains(&width),
+            "bitboard width must be 1..={MAX_WIDTH}"
+        );
+        BitBoard {
+            width,
+            height,
+            hidden,
+            full: u64::MAX >> (64 - width),
+            rows: vec![0; (height + hidden) as usize],
+            colors: vec![0; (width * (height + hidden)) as usize],
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn hidden_rows(&self) -> i32 {
+        self.hidden
+    }
+
+    /// Every cell color, vanishing zone included, laid out like
+    /// [`Board::cells`].
+    pub fn colors(&self) -> &[Cell] {
+        &self.colors
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        (0..self.width).contains(&x) && (-self.hidden..self.height).contains(&y)
+    }
+
+    /// The cell color at `(x, y)`, or 0 outside the board.
+    pub fn get(&self, x: i32, y: i32) -> Cell {
+        if !self.in_bounds(x, y) {
+            return 0;
+        }
+        self.colors[self.index(x, y)]
+    }
+
+    /// Writes a cell; writes outside the board are ignored.
+    pub fn set(&mut self, x: i32, y: i32, cell: Cell) {
+        if !self.in_bounds(x, y) {
+            return;
+        }
+        let idx = self.index(x, y);
+        self.colors[idx] = cell;
+        let row = &mut self.rows[(y + self.hidden) as usize];
+        if cell == 0 {
+            *row &= !(1 << x);
+        } else {
+            *row |= 1 << x;
+        }
+    }
+
+    /// Occupancy bits of row `y`; rows off the board read as empty.
+    pub fn row(&self, y: i32) -> u64 {
+        self.rows
+            .get((y + self.hidden) as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn is_row_full(&self, y: i32) -> bool {
+        self.row(y) == self.full
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|&r| r == 0)
+    }
+
+    /// Whether `mask` fits with its piece origin at `(x, y)`. Walls, the
+    /// floor and the ceiling above the vanishing zone block.
+    pub fn fits(&self, mask: &PieceMask, x: i32, y: i32) -> bool {
+        let left = x + mask.left;
+        if left < 0 || left + mask.width > self.width {
+            return false;
+        }
+        let top = y + mask.top + self.hidden;
+        for (i, &bits) in mask.rows[..mask.height].iter().enumerate() {
+            let bits = bits << left;
+            let row = top + i as i32;
+            if row < 0 || row >= self.rows.len() as i32 {
+                return false;
+            }
+            if self.rows[row as usize] & bits != 0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Removes full rows and returns them, top to bottom, in pre-clear
+    /// coordinates.
+    pub fn clear_lines(&mut self) -> Vec<i32> {
+        let w = self.width as usize;
+        let mut rows = Vec::new();
+        // Walk up from the floor, copying each kept row down to `to`.
+        let mut to = self.rows.len();
+        for from in (0..self.rows.len()).rev() {
+            if self.rows[from] == self.full {
+                rows.push(from as i32 - self.hidden);
+                continue;
+            }
+            to -= 1;
+            if to != from {
+                self.rows[to] = self.rows[from];
+                self.colors.copy_within(from * w..(from + 1) * w, to * w);
+            }
+        }
+        self.rows[..to].fill(0);
+        self.colors[..to * w].fill(0);
+        rows.reverse();
+        rows
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        ((y + self.hidden) * self.width + x) as usize
+    }
+}
+
+/// Panics if `board` is wider than [`MAX_WIDTH`]; see [`Game::bit_board`]
+/// for a checked conversion.
+///
+/// [`Game::bit_board`]: crate::Game::bit_board
+impl From<&Board> for BitBoard {
+    fn from(board: &Board) -> Self {
+        let mut bits = BitBoard::new(board.width(), board.height(), board.hidden_rows());
+        for y in -board.hidden_rows()..board.height() {
+            for x in 0..board.width() {
+                bits.set(x, y, board.get(x, y));
+            }
+        }
+        bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Piece, Srs};
+
+    fn board(rows: &[&str]) -> BitBoard {
+        let mut b = BitBoard::new(10, rows.len() as i32, 2);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    b.set(x as i32, y as i32, 8);
+                }
+            }
+        }
+        b
+    }
+
+    #[test]
+    fn fits_matches_per_cell_checks() {
+        let b = board(&["..........", "....#.....", "#...##...#", "##.####.##"]);
+        let occupied = |x: i32, y: i32| !b.in_bounds(x, y) || b.get(x, y) != 0;
+        for kind in Tetromino::ALL {
+            for rot in 0..4 {
+                let mask = PieceMask::of(&Srs, kind, rot);
+                for x in -3..12 {
+                    for y in -5..6 {
+                        let piece = Piece { kind, rot, x, y };
+                        assert_eq!(
+                            b.fits(&mask, x, y),
+                            Srs.fits(piece, &occupied),
+                            "{kind:?} {rot} at ({x}, {y})"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clear_lines_compacts_rows_and_colors() {
+        let mut b = board(&["#.........", "##########", ".#........", "##########"]);
+        b.set(0, -1, 3);
+        assert!(b.is_row_full(1));
+        assert_eq!(b.clear_lines(), vec![1, 3]);
+        assert_eq!(b.row(3), 0b10);
+        assert_eq!(b.row(2), 0b1);
+        assert_eq!(b.get(0, 1), 3);
+        assert_eq!(b.row(-1), 0);
+        assert!(!b.is_row_full(3));
+    }
+
+    #[test]
+    fn converts_from_board() {
+        let mut board = Board::new(10, 20, 2);
+        board.set(3, 19, 5);
+        board.set(0, -2, 1);
+        let bits = BitBoard::from(&board);
+        assert_eq!(bits.colors(), board.cells());
+        assert_eq!(bits.row(19), 0b1000);
+        assert_eq!(bits.row(-2), 0b1);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::Cell;
+
+/// The playfield: `width * height` visible cells plus `hidden` rows of
+/// vanishing zone above them, stored row by row, top row first.
+///
+/// Rows are numbered from the top of the visible area, so the vanishing
+/// zone covers `y` in `-hidden..0`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Board {
+    width: i32,
+    height: i32,
+    hidden: i32,
+    cells: Vec<Cell>,
+}
+
+impl Board {
+    pub fn new(width: i32, height: i32, hidden: i32) -> Self {
+        Self {
+            width,
+            height,
+            hidden,
+            cells: vec![0; (width * (height + hidden)) as usize],
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Visible rows only.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Rows in the vanishing zone above the visible area.
+    pub fn hidden_rows(&self) -> i32 {
+        self.hidden
+    }
+
+    /// Every cell, vanishing zone included.
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn visible_cells(&self) -> &[Cell] {
+        &self.cells[(self.hidden * self.width) as usize..]
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        (0..self.width).contains(&x) && (-self.hidden..self.height).contains(&y)
+    }
+
+    /// The cell at `(x, y)`, or 0 outside the board.
+    pub fn get(&self, x: i32, y: i32) -> Cell {
+        if !self.in_bounds(x, y) {
+            return 0;
+        }
+        self.cells[self.index(x, y)]
+    }
+
+    /// Writes a cell; writes outside the board are ignored.
+    pub fn set(&mut self, x: i32, y: i32, cell: Cell) {
+        if self.in_bounds(x, y) {
+            let idx = self.index(x, y);
+            self.cells[idx] = cell;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.fill(0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|&c| c == 0)
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        ((y + self.hidden) * self.width + x) as usize
+    }
+
+    /// Full rows, top to bottom.
+    pub fn full_rows(&self) -> Vec<i32> {
+        (-self.hidden..self.height)
+            .filter(|&y| (0..self.width).all(|x| self.cells[self.index(x, y)] != 0))
+            .collect()
+    }
+
+    /// Removes full rows and returns them, top to bottom, in pre-clear
+    /// coordinates.
+    ///
+    /// One pass from the floor up copies every kept row straight to its
+    /// final place, however many rows are cleared.
+    pub fn clear_lines(&mut self) -> Vec<i32> {
+        let w = self.width as usize;
+        let mut rows = Vec::new();
+        // Storage rows; reported rows are shifted back by `hidden`.
+        let mut to = self.cells.len() / w.max(1);
+        for from in (0..to).rev() {
+            let row = from * w..(from + 1) * w;
+            if self.cells[row.clone()].iter().all(|&c| c != 0) {
+                rows.push(from as i32 - self.hidden);
+                continue;
+            }
+            to -= 1;
+            if to != from {
+                self.cells.copy_within(row, to * w);
+            }
+        }
+        self.cells[..to * w].fill(0);
+
+        rows.reverse();
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_lines_compacts_in_one_pass() {
+        let mut board = Board::new(13, 6, 1);
+        let full = [1, 2, 4];
+        for y in -1..6 {
+            for x in 0..13 {
+                if full.contains(&y) || x == y {
+                    board.set(x, y, 1 + y as u8 % 7);
+                }
+            }
+        }
+
+        assert_eq!(board.clear_lines(), full);
+        // Rows 5, 3 and 0 keep their order at the bottom; everything above
+        // moved down by three.
+        for (y, from) in [(5, 5), (4, 3), (3, 0), (2, -1)] {
+            for x in 0..13 {
+                assert_eq!(board.get(x, y) != 0, x == from, "row {y} column {x}");
+            }
+        }
+        assert!((-1..2).all(|y| (0..13).all(|x| board.get(x, y) == 0)));
+        assert!(board.clear_lines().is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::{FRAMES_PER_SECOND, Game, LockEvent, Step};
+
+/// Everything a player (or a replay, a remote peer, a bot) can ask a game
+/// to do. Run one with [`Game::apply`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Command {
+    Left,
+    Right,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    /// One manual gravity step, see [`Game::tick`].
+    Tick,
+}
+
+impl Command {
+    pub const ALL: [Command; 9] = [
+        Command::Left,
+        Command::Right,
+        Command::RotateCw,
+        Command::RotateCcw,
+        Command::Rotate180,
+        Command::SoftDrop,
+        Command::HardDrop,
+        Command::Hold,
+        Command::Tick,
+    ];
+}
+
+/// A command stamped with the frame it runs on.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimedCommand {
+    pub frame: u64,
+    pub command: Command,
+}
+
+/// Length of one frame at [`FRAMES_PER_SECOND`].
+pub fn frame_duration() -> Duration {
+    Duration::from_secs_f64(1.0 / FRAMES_PER_SECOND)
+}
+
+/// What one [`InputBuffer::step`] did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frame {
+    /// The commands applied, in order; feed these to a replay.
+    pub commands: Vec<TimedCommand>,
+    /// Every step the commands and the frame's update returned, in order.
+    pub steps: Vec<Step>,
+}
+
+impl Frame {
+    /// Pieces that locked during the frame.
+    pub fn locks(&self) -> impl Iterator<Item = &LockEvent> {
+        self.steps.iter().filter_map(|step| match step {
+            Step::Locked(event) => Some(event),
+            _ => None,
+        })
+    }
+}
+
+/// Commands waiting for their frame, and the frame clock that runs them.
+///
+/// Each [`step`](Self::step) applies the commands due on the current frame,
+/// in the order they were pushed, then advances the game by one frame.
+/// Everything that reaches the game goes through here, so what `step`
+/// returns is a complete record for replays and netplay, along with every
+/// lock.
+#[derive(Debug, Clone, Default)]
+pub struct InputBuffer {
+    frame: u64,
+    pending: VecDeque<TimedCommand>,
+    // Commands run by `apply` since the last step, and what they returned.
+    applied: Frame,
+}
+
+impl InputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The frame the next `step` runs.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Queues `command` for `frame`. Commands for a frame that has already
+    /// run go out on the next one.
+    pub fn push(&mut self, frame: u64, command: Command) {
+        let frame = frame.max(self.frame);
+        // Keep the queue sorted, after anything already queued for the frame.
+        let at = self.pending.partition_point(|c| c.frame <= frame);
+        self.pending.insert(at, TimedCommand { frame, command });
+    }
+
+    /// Queues `command` for the current frame.
+    pub fn push_now(&mut self, command: Command) {
+        self.push(self.frame, command);
+    }
+
+    /// Runs `command` right away, as part of the current frame. For callers
+    /// that need to see the result, like auto shift.
+    pub fn apply(&mut self, game: &mut Game, command: Command) -> Option<Step> {
+        let frame = self.frame;
+        self.applied.commands.push(TimedCommand { frame, command });
+        let step = game.apply(command);
+        self.applied.steps.extend(step.clone());
+        step
+    }
+
+    /// Drops every queued command.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Runs one frame and returns the commands it applied, starting with
+    /// the ones run by `apply` during the frame, and the steps they caused.
+    pub fn step(&mut self, game: &mut Game) -> Frame {
+        let mut applied = std::mem::take(&mut self.applied);
+        while let Some(&next) = self.pending.front() {
+            if next.frame > self.frame {
+                break;
+            }
+            self.pending.pop_front();
+            applied.steps.extend(game.apply(next.command));
+            applied.commands.push(next);
+        }
+        applied.steps.extend(game.update(frame_duration()));
+        self.frame += 1;
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_run_on_their_frame_in_push_order() {
+        let mut g = Game::with_seed(1);
+        let mut input = InputBuffer::new();
+        input.push(2, Command::Left);
+        input.push(1, Command::RotateCw);
+        input.push(1, Command::Left);
+        let x = g.current_piece().x;
+
+        assert!(input.step(&mut g).commands.is_empty());
+        let frame = input.step(&mut g);
+        assert_eq!(
+            frame.commands.iter().map(|c| c.command).collect::<Vec<_>>(),
+            [Command::RotateCw, Command::Left]
+        );
+        assert_eq!(input.step(&mut g).commands.len(), 1);
+        assert_eq!(g.current_piece().rot, 1);
+        assert!(g.current_piece().x < x);
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn late_commands_run_on_the_next_frame() {
+        let mut g = Game::with_seed(1);
+        let mut input = InputBuffer::new();
+        input.step(&mut g);
+        input.step(&mut g);
+        input.push(0, Command::HardDrop);
+
+        let frame = input.step(&mut g);
+        assert_eq!(frame.commands[0].frame, 2);
+        assert_eq!(input.frame(), 3);
+    }
+
+    #[test]
+    fn step_reports_locks() {
+        let mut g = Game::with_seed(1);
+        let mut input = InputBuffer::new();
+        input.push_now(Command::HardDrop);
+        input.apply(&mut g, Command::Left);
+        let frame = input.step(&mut g);
+        assert_eq!(frame.commands.len(), 2);
+        assert_eq!(frame.locks().count(), 1);
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{GameOverReason, LockEvent, Piece, Tetromino};
+
+/// Something that happened inside a `Game`, in the order it happened.
+/// Collect them with `Game::drain_events`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Event {
+    /// A new piece entered the board, either from the queue or from hold.
+    PieceSpawned(Piece),
+    /// The current piece shifted sideways or fell; a hard drop or 20G fall
+    /// is reported once, at its final position.
+    PieceMoved(Piece),
+    PieceRotated(Piece),
+    PieceLocked(LockEvent),
+    /// Rows removed by the last lock, top to bottom.
+    LinesCleared(Vec<i32>),
+    /// The level went up to the given value.
+    LevelUp(u32),
+    /// The given piece went into the hold slot.
+    Hold(Tetromino),
+    GameOver(GameOverReason),
+}
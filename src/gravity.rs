@@ -0,0 +1,131 @@
+//! Gravity is measured in G: rows fallen per frame at [`FRAMES_PER_SECOND`].
+
+/// Frame rate the gravity tables are written for.
+pub const FRAMES_PER_SECOND: f64 = 60.0;
+
+/// At this speed pieces drop straight to the floor as they spawn.
+pub const MAX_GRAVITY: f64 = 20.0;
+
+// NES NTSC frames per row for levels 0..=28; level 29 and up is 1 frame.
+const NES_FRAMES_PER_ROW: [u32; 29] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+// TGM internal gravity in 1/256 G, as (first level, value) steps.
+const TGM_INTERNAL_GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+/// Maps a level to a gravity in G.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GravityCurve {
+    /// Guideline formula: `(0.8 - (level - 1) * 0.007) ^ (level - 1)`
+    /// seconds per row, for levels starting at 1.
+    #[default]
+    Guideline,
+    /// NES frames-per-row table, for levels starting at 0.
+    Nes,
+    /// TGM internal gravity table. TGM levels count up to 999, so pair this
+    /// with a small `lines_per_level` or a high start level.
+    Tgm,
+    /// `(first level, G)` steps in ascending level order; levels below the
+    /// first step use its value.
+    Custom(Vec<(u32, f64)>),
+}
+
+impl GravityCurve {
+    /// Gravity at `level`, capped at `MAX_GRAVITY`.
+    pub fn gravity(&self, level: u32) -> f64 {
+        let g = match self {
+            GravityCurve::Guideline => {
+                let n = level.saturating_sub(1).min(30) as f64;
+                let seconds_per_row = (0.8 - n * 0.007).powf(n);
+                1.0 / (seconds_per_row * FRAMES_PER_SECOND)
+            }
+            GravityCurve::Nes => {
+                let frames = NES_FRAMES_PER_ROW.get(level as usize).copied().unwrap_or(1);
+                1.0 / frames as f64
+            }
+            GravityCurve::Tgm => {
+                let steps = TGM_INTERNAL_GRAVITY
+                    .iter()
+                    .map(|&(l, g)| (l, g as f64 / 256.0));
+                step_value(steps, level)
+            }
+            GravityCurve::Custom(steps) => step_value(steps.iter().copied(), level),
+        };
+        g.min(MAX_GRAVITY)
+    }
+}
+
+fn step_value(steps: impl Iterator<Item = (u32, f64)>, level: u32) -> f64 {
+    let mut value = None;
+    for (first, g) in steps {
+        if first > level && value.is_some() {
+            break;
+        }
+        value = Some(g);
+    }
+    value.unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guideline_level_one_is_one_row_per_second() {
+        let g = GravityCurve::Guideline.gravity(1);
+        assert!((g * FRAMES_PER_SECOND - 1.0).abs() < 1e-9);
+        assert_eq!(GravityCurve::Guideline.gravity(25), MAX_GRAVITY);
+    }
+
+    #[test]
+    fn nes_uses_frame_table() {
+        assert_eq!(GravityCurve::Nes.gravity(0), 1.0 / 48.0);
+        assert_eq!(GravityCurve::Nes.gravity(19), 0.5);
+        assert_eq!(GravityCurve::Nes.gravity(29), 1.0);
+    }
+
+    #[test]
+    fn step_tables_pick_last_reached_step() {
+        assert_eq!(GravityCurve::Tgm.gravity(0), 4.0 / 256.0);
+        assert_eq!(GravityCurve::Tgm.gravity(205), 4.0 / 256.0);
+        assert_eq!(GravityCurve::Tgm.gravity(999), MAX_GRAVITY);
+
+        let custom = GravityCurve::Custom(vec![(5, 0.5), (10, 2.0)]);
+        assert_eq!(custom.gravity(1), 0.5);
+        assert_eq!(custom.gravity(9), 0.5);
+        assert_eq!(custom.gravity(12), 2.0);
+    }
+}
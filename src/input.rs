@@ -0,0 +1,357 @@
+use std::time::Duration;
+
+use crate::{Command, FRAMES_PER_SECOND, Game, InputBuffer, MAX_GRAVITY, Step};
+
+/// Auto shift and soft drop tuning.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Handling {
+    /// Delayed Auto Shift: how long a direction is held before it repeats.
+    pub das: Duration,
+    /// Auto Repeat Rate: time between repeated shifts. Zero moves the piece
+    /// to the wall at once.
+    pub arr: Duration,
+    /// Soft drop speed as a multiple of the current gravity.
+    /// `f64::INFINITY` drops straight to the floor.
+    pub sdf: f64,
+    /// DAS cut: after a new piece spawns, a charged DAS waits this long
+    /// before it shifts the piece.
+    pub das_cut: Duration,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling {
+            das: Duration::from_micros(166_667),
+            arr: Duration::from_micros(33_333),
+            sdf: 20.0,
+            das_cut: Duration::ZERO,
+        }
+    }
+}
+
+/// Keys the controller keeps track of while they are held.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Key {
+    Left,
+    Right,
+    SoftDrop,
+}
+
+/// Turns key presses, releases and elapsed time into moves on a [`Game`].
+///
+/// Call [`press`](Self::press) and [`release`](Self::release) as input
+/// arrives and [`update`](Self::update) once per frame, before
+/// [`InputBuffer::step`]. Every move goes through the input buffer, so it
+/// shows up in what `step` returns. When both directions are held the last one pressed wins.
+#[derive(Debug, Clone)]
+pub struct InputController {
+    handling: Handling,
+    left: bool,
+    right: bool,
+    soft_drop: bool,
+    // -1, 0 or 1: the direction being shifted.
+    direction: i32,
+    // How long `direction` has been held, including DAS cut pauses.
+    held_for: Duration,
+    charged: bool,
+    // Time owed to the next auto repeat shift.
+    repeat: Duration,
+    drop_progress: f64,
+    pieces_spawned: u32,
+}
+
+impl Default for InputController {
+    fn default() -> Self {
+        Self::new(Handling::default())
+    }
+}
+
+impl InputController {
+    pub fn new(handling: Handling) -> Self {
+        InputController {
+            handling,
+            left: false,
+            right: false,
+            soft_drop: false,
+            direction: 0,
+            held_for: Duration::ZERO,
+            charged: false,
+            repeat: Duration::ZERO,
+            drop_progress: 0.0,
+            pieces_spawned: 0,
+        }
+    }
+
+    pub fn handling(&self) -> Handling {
+        self.handling
+    }
+
+    pub fn set_handling(&mut self, handling: Handling) {
+        self.handling = handling;
+    }
+
+    pub fn is_held(&self, key: Key) -> bool {
+        match key {
+            Key::Left => self.left,
+            Key::Right => self.right,
+            Key::SoftDrop => self.soft_drop,
+        }
+    }
+
+    /// A key went down. Directions shift one cell right away and start
+    /// charging DAS.
+    pub fn press(&mut self, game: &mut Game, input: &mut InputBuffer, key: Key) {
+        match key {
+            Key::Left if !self.left => {
+                self.left = true;
+                self.start_shift(-1);
+                shift(game, input, -1);
+            }
+            Key::Right if !self.right => {
+                self.right = true;
+                self.start_shift(1);
+                shift(game, input, 1);
+            }
+            Key::SoftDrop if !self.soft_drop => {
+                self.soft_drop = true;
+                self.drop_progress = 0.0;
+            }
+            _ => {}
+        }
+        self.pieces_spawned = game.pieces_spawned();
+    }
+
+    /// A key came up. Letting go of one direction while the other is still
+    /// held hands control back to it with a fresh DAS.
+    pub fn release(&mut self, key: Key) {
+        match key {
+            Key::Left => {
+                self.left = false;
+                if self.direction == -1 {
+                    self.start_shift(if self.right { 1 } else { 0 });
+                }
+            }
+            Key::Right => {
+                self.right = false;
+                if self.direction == 1 {
+                    self.start_shift(if self.left { -1 } else { 0 });
+                }
+            }
+            Key::SoftDrop => self.soft_drop = false,
+        }
+    }
+
+    /// Advances held keys by `dt`: auto shifts once DAS is charged and soft
+    /// drops at `sdf` times gravity.
+    pub fn update(&mut self, game: &mut Game, input: &mut InputBuffer, dt: Duration) {
+        if game.pieces_spawned() != self.pieces_spawned {
+            self.pieces_spawned = game.pieces_spawned();
+            self.cut_das();
+        }
+        self.update_shift(game, input, dt);
+        self.update_soft_drop(game, input, dt);
+    }
+
+    fn start_shift(&mut self, direction: i32) {
+        self.direction = direction;
+        self.held_for = Duration::ZERO;
+        self.charged = false;
+        self.repeat = Duration::ZERO;
+    }
+
+    // A new piece must not fly off with DAS charged for the previous one
+    // before the player had a chance to react.
+    fn cut_das(&mut self) {
+        let Handling { das, das_cut, .. } = self.handling;
+        if self.charged && !das_cut.is_zero() {
+            self.held_for = das.saturating_sub(das_cut);
+            self.charged = false;
+            self.repeat = Duration::ZERO;
+        }
+    }
+
+    fn update_shift(&mut self, game: &mut Game, input: &mut InputBuffer, dt: Duration) {
+        if self.direction == 0 {
+            return;
+        }
+        let Handling { das, arr, .. } = self.handling;
+
+        self.held_for += dt;
+        if self.held_for < das {
+            return;
+        }
+        if self.charged {
+            self.repeat += dt;
+        } else {
+            // Charging the DAS gives one shift right away; whatever time is
+            // left over counts towards the next repeat.
+            self.charged = true;
+            self.repeat = self.held_for - das;
+            if !shift(game, input, self.direction) {
+                return;
+            }
+        }
+
+        if arr.is_zero() {
+            while shift(game, input, self.direction) {}
+            self.repeat = Duration::ZERO;
+            return;
+        }
+        while self.repeat >= arr {
+            self.repeat -= arr;
+            if !shift(game, input, self.direction) {
+                break;
+            }
+        }
+    }
+
+    fn update_soft_drop(&mut self, game: &mut Game, input: &mut InputBuffer, dt: Duration) {
+        if !self.soft_drop {
+            return;
+        }
+        let speed = game.gravity() * self.handling.sdf;
+        if speed >= MAX_GRAVITY {
+            while input.apply(game, Command::SoftDrop) == Some(Step::Moved) {}
+            self.drop_progress = 0.0;
+            return;
+        }
+
+        self.drop_progress += speed * FRAMES_PER_SECOND * dt.as_secs_f64();
+        while self.drop_progress >= 1.0 {
+            self.drop_progress -= 1.0;
+            if input.apply(game, Command::SoftDrop) != Some(Step::Moved) {
+                self.drop_progress = 0.0;
+                break;
+            }
+        }
+    }
+}
+
+// Moves the piece one cell sideways; false when it could not move.
+fn shift(game: &mut Game, input: &mut InputBuffer, direction: i32) -> bool {
+    let before = game.current_piece();
+    let command = match direction {
+        -1 => Command::Left,
+        1 => Command::Right,
+        _ => return false,
+    };
+    input.apply(game, command);
+    game.current_piece() != before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn handling(das: u64, arr: u64) -> Handling {
+        Handling {
+            das: ms(das),
+            arr: ms(arr),
+            ..Handling::default()
+        }
+    }
+
+    #[test]
+    fn das_then_arr() {
+        let mut g = Game::with_seed(1);
+        let mut buffer = InputBuffer::new();
+        let mut controls = InputController::new(handling(100, 20));
+        let x = g.current_piece().x;
+
+        controls.press(&mut g, &mut buffer, Key::Left);
+        assert_eq!(g.current_piece().x, x - 1);
+        controls.update(&mut g, &mut buffer, ms(99));
+        assert_eq!(g.current_piece().x, x - 1);
+        controls.update(&mut g, &mut buffer, ms(1));
+        assert_eq!(g.current_piece().x, x - 2);
+        controls.update(&mut g, &mut buffer, ms(20));
+        assert_eq!(g.current_piece().x, x - 3);
+
+        controls.release(Key::Left);
+        controls.update(&mut g, &mut buffer, ms(100));
+        assert_eq!(g.current_piece().x, x - 3);
+    }
+
+    #[test]
+    fn zero_arr_shifts_to_the_wall() {
+        let mut g = Game::with_seed(1);
+        let mut buffer = InputBuffer::new();
+        let mut controls = InputController::new(handling(100, 0));
+
+        controls.press(&mut g, &mut buffer, Key::Right);
+        controls.update(&mut g, &mut buffer, ms(100));
+        let max_x = g.blocks(g.current_piece()).iter().map(|b| b.0).max();
+        assert_eq!(max_x, Some(g.width() - 1));
+    }
+
+    #[test]
+    fn last_direction_pressed_wins() {
+        let mut g = Game::with_seed(1);
+        let mut buffer = InputBuffer::new();
+        let mut controls = InputController::new(handling(100, 0));
+        let x = g.current_piece().x;
+
+        controls.press(&mut g, &mut buffer, Key::Left);
+        controls.press(&mut g, &mut buffer, Key::Right);
+        assert_eq!(g.current_piece().x, x);
+        controls.update(&mut g, &mut buffer, ms(50));
+        // Letting go of right goes back to left, which charges from scratch.
+        controls.release(Key::Right);
+        controls.update(&mut g, &mut buffer, ms(99));
+        assert_eq!(g.current_piece().x, x);
+        controls.update(&mut g, &mut buffer, ms(1));
+        let min_x = g.blocks(g.current_piece()).iter().map(|b| b.0).min();
+        assert_eq!(min_x, Some(0));
+    }
+
+    #[test]
+    fn das_cut_holds_shift_on_a_new_piece() {
+        let mut g = Game::with_seed(1);
+        let mut buffer = InputBuffer::new();
+        let mut controls = InputController::new(Handling {
+            das_cut: ms(50),
+            ..handling(100, 0)
+        });
+
+        controls.press(&mut g, &mut buffer, Key::Left);
+        controls.update(&mut g, &mut buffer, ms(100));
+        g.hard_drop();
+        let x = g.current_piece().x;
+        controls.update(&mut g, &mut buffer, ms(49));
+        assert_eq!(g.current_piece().x, x);
+        controls.update(&mut g, &mut buffer, ms(1));
+        assert!(g.current_piece().x < x);
+    }
+
+    #[test]
+    fn soft_drop_factor_scales_gravity() {
+        let mut g = Game::with_seed(1);
+        let mut buffer = InputBuffer::new();
+        let mut controls = InputController::new(Handling {
+            sdf: 6.0,
+            ..Handling::default()
+        });
+        let y = g.current_piece().y;
+
+        controls.press(&mut g, &mut buffer, Key::SoftDrop);
+        // Level 1 guideline gravity is one row per 60 frames, so 10 frames
+        // at 6x gravity is one row.
+        controls.update(
+            &mut g,
+            &mut buffer,
+            Duration::from_secs_f64(10.5 / FRAMES_PER_SECOND),
+        );
+        assert_eq!(g.current_piece().y, y + 1);
+
+        controls.set_handling(Handling {
+            sdf: f64::INFINITY,
+            ..controls.handling()
+        });
+        controls.update(&mut g, &mut buffer, ms(1));
+        assert_eq!(g.current_piece(), g.ghost_piece());
+    }
+}
@@ -1,24 +1,97 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
 use rand::prelude::*;
+use rand_chacha::ChaCha12Rng;
+
+pub mod bitboard;
+pub mod board;
+pub mod command;
+pub mod event;
+pub mod gravity;
+pub mod input;
+pub mod randomizer;
+pub mod replay;
+pub mod rotation;
+pub mod scoring;
+pub mod spin;
+pub mod undo;
 
+pub use bitboard::{BitBoard, PieceMask};
+pub use board::Board;
+pub use command::{Command, Frame, InputBuffer, TimedCommand};
+pub use event::Event;
+pub use gravity::{FRAMES_PER_SECOND, GravityCurve, MAX_GRAVITY};
+pub use input::{Handling, InputController, Key};
+pub use randomizer::{Bag, NesRandomizer, PureRandom, Randomizer, TgmHistory};
+pub use replay::{Replay, ReplayError, ReplayPlayer};
+pub use rotation::{Ars, Nrs, RotationSystem, Srs};
+pub use scoring::ScoringRule;
+pub use spin::TSpin;
+pub use undo::{Snapshot, UndoStack};
+
+/// Default playfield size; see `GameConfig::width` and `GameConfig::height`.
 pub const BOARD_W: i32 = 10;
 pub const BOARD_H: i32 = 20;
 
+/// Largest width, height and vanishing zone a `Game` accepts.
+pub const MAX_BOARD_SIZE: i32 = 1024;
+/// Longest next queue a `Game` shows.
+pub const MAX_PREVIEW: usize = 32;
+/// Highest level a `Game` can start on.
+pub const MAX_START_LEVEL: u32 = 999;
+
 // Cell value meanings:
 // 0 = empty
 // 1..=7 = a tetromino kind (also used for coloring)
 pub type Cell = u8;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Step {
     /// Piece moved down by 1.
     Moved,
+    /// Piece is resting on the stack, waiting for its lock delay to run out.
+    Grounded,
     /// Piece locked, optionally cleared lines, then a new piece spawned.
-    Locked { cleared: u32, game_over: bool },
+    Locked(LockEvent),
     /// Game was already over, no-op.
     GameOver,
+    /// A line clear delay or ARE is running and there is no piece in play;
+    /// only the delay advanced.
+    Waiting,
+}
+
+/// Everything that happened when a piece locked.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LockEvent {
+    /// The piece where it locked.
+    pub piece: Piece,
+    /// Cleared rows, top to bottom, as they were numbered before the clear.
+    pub rows: Vec<i32>,
+    pub spin: TSpin,
+    /// Combo counter after this lock, see `Game::combo`.
+    pub combo: Option<u32>,
+    /// Whether this clear continued a back-to-back chain.
+    pub back_to_back: bool,
+    pub points: u32,
+    pub perfect_clear: bool,
+    pub level_up: bool,
+    /// The game ended with this lock, by lock out or, when the next piece
+    /// spawns without delay, by it failing to spawn.
+    pub game_over: bool,
+}
+
+impl LockEvent {
+    pub fn cleared(&self) -> u32 {
+        self.rows.len() as u32
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Tetromino {
     I = 1,
     O = 2,
@@ -30,12 +103,23 @@ pub enum Tetromino {
 }
 
 impl Tetromino {
+    pub const ALL: [Tetromino; 7] = [
+        Tetromino::I,
+        Tetromino::O,
+        Tetromino::T,
+        Tetromino::S,
+        Tetromino::Z,
+        Tetromino::J,
+        Tetromino::L,
+    ];
+
     pub fn id(self) -> Cell {
         self as Cell
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
     pub kind: Tetromino,
     pub rot: u8,
@@ -43,126 +127,506 @@ pub struct Piece {
     pub y: i32,
 }
 
-// 4 rotations, each with 4 blocks, in a 4x4 local grid.
-// (dx, dy) are offsets from the piece's (x, y) origin.
-const SHAPES: [[[(i8, i8); 4]; 4]; 7] = [
-    // I
-    [
-        [(0, 1), (1, 1), (2, 1), (3, 1)],
-        [(2, 0), (2, 1), (2, 2), (2, 3)],
-        [(0, 2), (1, 2), (2, 2), (3, 2)],
-        [(1, 0), (1, 1), (1, 2), (1, 3)],
-    ],
-    // O
-    [
-        [(1, 0), (2, 0), (1, 1), (2, 1)],
-        [(1, 0), (2, 0), (1, 1), (2, 1)],
-        [(1, 0), (2, 0), (1, 1), (2, 1)],
-        [(1, 0), (2, 0), (1, 1), (2, 1)],
-    ],
-    // T
-    [
-        [(1, 0), (0, 1), (1, 1), (2, 1)],
-        [(1, 0), (1, 1), (2, 1), (1, 2)],
-        [(0, 1), (1, 1), (2, 1), (1, 2)],
-        [(1, 0), (0, 1), (1, 1), (1, 2)],
-    ],
-    // S
-    [
-        [(1, 0), (2, 0), (0, 1), (1, 1)],
-        [(1, 0), (1, 1), (2, 1), (2, 2)],
-        [(1, 1), (2, 1), (0, 2), (1, 2)],
-        [(0, 0), (0, 1), (1, 1), (1, 2)],
-    ],
-    // Z
-    [
-        [(0, 0), (1, 0), (1, 1), (2, 1)],
-        [(2, 0), (1, 1), (2, 1), (1, 2)],
-        [(0, 1), (1, 1), (1, 2), (2, 2)],
-        [(1, 0), (0, 1), (1, 1), (0, 2)],
-    ],
-    // J
-    [
-        [(0, 0), (0, 1), (1, 1), (2, 1)],
-        [(1, 0), (2, 0), (1, 1), (1, 2)],
-        [(0, 1), (1, 1), (2, 1), (2, 2)],
-        [(1, 0), (1, 1), (0, 2), (1, 2)],
-    ],
-    // L
-    [
-        [(2, 0), (0, 1), (1, 1), (2, 1)],
-        [(1, 0), (1, 1), (1, 2), (2, 2)],
-        [(0, 1), (1, 1), (2, 1), (0, 2)],
-        [(0, 0), (1, 0), (1, 1), (1, 2)],
-    ],
-];
-
-fn shape_index(kind: Tetromino) -> usize {
-    (kind.id() as usize) - 1
+/// How moves and rotations interact with the lock delay timer.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LockReset {
+    /// Each successful move or rotation on the ground restarts the timer, at
+    /// most `limit` times before the piece reaches a new lowest row.
+    Move { limit: u32 },
+    /// Every successful move or rotation restarts the timer.
+    Infinite,
+    /// Only falling to a new lowest row restarts the timer.
+    Step,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LockDelay {
+    /// How long a grounded piece may rest before it locks. Zero locks as soon
+    /// as the piece cannot fall any further.
+    pub duration: Duration,
+    pub reset: LockReset,
+}
+
+impl Default for LockDelay {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_millis(500),
+            reset: LockReset::Move { limit: 15 },
+        }
+    }
+}
+
+/// A rotation direction.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Turn {
+    Cw,
+    Ccw,
+    Half,
+}
+
+impl Turn {
+    /// Clockwise quarter turns this rotation amounts to.
+    pub fn quarter_turns(self) -> u8 {
+        match self {
+            Turn::Cw => 1,
+            Turn::Half => 2,
+            Turn::Ccw => 3,
+        }
+    }
+}
+
+/// What a `Game` is doing right now, see `Game::phase`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Phase {
+    /// The current piece can still fall.
+    Falling,
+    /// The current piece is on the ground and its lock delay is running.
+    LockDelay,
+    /// Full rows stay on the board until the line clear delay runs out.
+    /// `rows` are numbered like `LockEvent::rows`.
+    LineClear {
+        rows: Vec<i32>,
+        remaining: Duration,
+    },
+    /// Entry delay before the next piece spawns.
+    Are {
+        remaining: Duration,
+    },
+    GameOver,
+}
+
+// A pause between one piece locking and the next spawning.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Delay {
+    LineClear { rows: Vec<i32>, remaining: Duration },
+    Are { remaining: Duration },
+}
+
+/// Why a game ended.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameOverReason {
+    /// A new piece overlapped the stack where it spawned.
+    BlockOut,
+    /// A piece locked entirely above the visible field.
+    LockOut,
+    /// A piece locked with at least one block above the visible field.
+    PartialLockOut,
+}
+
+/// Which guideline top-out conditions end the game. Block out always does,
+/// since play cannot continue.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TopOutRules {
+    pub lock_out: bool,
+    pub partial_lock_out: bool,
+}
+
+impl Default for TopOutRules {
+    fn default() -> Self {
+        Self {
+            lock_out: true,
+            partial_lock_out: false,
+        }
+    }
 }
-// Todo
-fn blocks_for(piece: Piece) -> [(i32, i32); 4] {
-    let rot = (piece.rot % 4) as usize;
-    let shape = &SHAPES[shape_index(piece.kind)][rot];
-    let mut out = [(0, 0); 4];
-    for (i, (dx, dy)) in shape.iter().enumerate() {
-        out[i] = (piece.x + (*dx as i32), piece.y + (*dy as i32));
-    }
-    out
+
+/// Rules a `Game` is built with.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameConfig {
+    /// Visible playfield size in cells, from 4 up to [`MAX_BOARD_SIZE`]
+    /// each way.
+    pub width: i32,
+    pub height: i32,
+    /// Rows of vanishing zone stacked on top of the visible field. Blocks
+    /// that lock up there are kept. Raised to however many rows the rotation
+    /// system spawns pieces in, and at most [`MAX_BOARD_SIZE`].
+    pub hidden_rows: i32,
+    #[cfg_attr(feature = "serde", serde(with = "rotation::serde_name"))]
+    pub rotation: Arc<dyn RotationSystem>,
+    /// Template for the piece randomizer; each game starts from a copy.
+    #[cfg_attr(feature = "serde", serde(with = "randomizer::serde_name"))]
+    pub randomizer: Box<dyn Randomizer>,
+    /// How many upcoming pieces the next queue shows, from 1 up to
+    /// [`MAX_PREVIEW`].
+    pub preview: usize,
+    pub lock_delay: LockDelay,
+    pub gravity: GravityCurve,
+    /// Level shown before any lines are cleared, at most
+    /// [`MAX_START_LEVEL`].
+    pub start_level: u32,
+    pub lines_per_level: u32,
+    pub scoring: ScoringRule,
+    pub top_out: TopOutRules,
+    /// Entry delay (ARE) between a piece locking and the next one spawning.
+    pub are: Duration,
+    /// How long cleared rows stay on the board before they vanish, on top
+    /// of `are`.
+    pub line_clear_delay: Duration,
+    /// Move a freshly spawned piece down one row right away when nothing is
+    /// in the way, as the guideline does.
+    pub spawn_drop: bool,
+    /// Queue up `Event`s for `Game::drain_events`. Off by default; only turn
+    /// it on if something drains them, or the queue grows without bound.
+    pub record_events: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            width: BOARD_W,
+            height: BOARD_H,
+            hidden_rows: 20,
+            rotation: Arc::new(Srs),
+            randomizer: Box::new(Bag::seven()),
+            preview: 5,
+            lock_delay: LockDelay::default(),
+            gravity: GravityCurve::Guideline,
+            start_level: 1,
+            lines_per_level: 10,
+            scoring: ScoringRule::Guideline,
+            top_out: TopOutRules::default(),
+            are: Duration::ZERO,
+            line_clear_delay: Duration::ZERO,
+            spawn_drop: true,
+            record_events: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Game {
-    board: Vec<Cell>,
+    config: GameConfig,
+    board: Board,
     current: Piece,
-    next: Tetromino,
-    rng: StdRng,
+    queue: VecDeque<Tetromino>,
+    held: Option<Tetromino>,
+    // Set once the current piece has been swapped into hold; cleared on lock.
+    hold_used: bool,
+    // IRS/IHS inputs waiting for the next spawn.
+    initial_rotation: Option<Turn>,
+    initial_hold: bool,
+    pieces_spawned: u32,
+    // Time spent on the ground, `None` while the piece can still fall.
+    lock_timer: Option<Duration>,
+    lock_resets: u32,
+    // Deepest row the current piece's origin has reached.
+    lowest_y: i32,
+    // Fraction of a row gravity has built up towards the next fall.
+    fall_progress: f64,
+    // Kick used by the last successful rotation, cleared by any other move.
+    last_kick: Option<(i32, i32)>,
+    // Set while nothing is in play between two pieces.
+    delay: Option<Delay>,
+    #[cfg_attr(feature = "serde", serde(with = "randomizer::serde_state"))]
+    randomizer: Box<dyn Randomizer>,
+    seed: u64,
+    // ChaCha12 yields the same stream on every platform and can be saved.
+    rng: ChaCha12Rng,
     score: u32,
     lines: u32,
-    game_over: bool,
+    // Guideline combo counter: `Some(0)` after the first of a run of clears.
+    combo: Option<u32>,
+    // Whether the last line clear was a Tetris or a T-spin.
+    back_to_back: bool,
+    game_over: Option<GameOverReason>,
+    events: Vec<Event>,
+}
+
+// Rows of vanishing zone `rotation` needs to spawn every piece in, since
+// the zone is capped by a ceiling.
+fn spawn_rows(rotation: &dyn RotationSystem, width: i32) -> i32 {
+    Tetromino::ALL
+        .iter()
+        .map(|&kind| {
+            let piece = rotation.spawn(kind, width);
+            let shape = rotation.shape(kind, piece.rot);
+            let top = shape.iter().map(|&(_, dy)| dy as i32).min().unwrap_or(0);
+            -(piece.y + top)
+        })
+        .max()
+        .unwrap_or(0)
+        .max(0)
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Game {
     pub fn new() -> Self {
-        // StdRng is deterministic; seed from the OS to vary each run.
-        let seed: u64 = rand::random();
+        Self::with_config(GameConfig::default())
+    }
+
+    /// A default game whose piece sequence is fully determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_config_and_seed(GameConfig::default(), seed)
+    }
+
+    pub fn with_config(config: GameConfig) -> Self {
+        // The RNG is deterministic; seed from the OS to vary each run.
+        Self::with_config_and_seed(config, rand::random())
+    }
+
+    pub fn with_config_and_seed(mut config: GameConfig, seed: u64) -> Self {
+        config.width = config.width.clamp(4, MAX_BOARD_SIZE);
+        config.height = config.height.clamp(4, MAX_BOARD_SIZE);
+        let spawn_rows = spawn_rows(&*config.rotation, config.width);
+        config.hidden_rows = config.hidden_rows.clamp(spawn_rows, MAX_BOARD_SIZE);
+        config.preview = config.preview.clamp(1, MAX_PREVIEW);
+        config.start_level = config.start_level.min(MAX_START_LEVEL);
+        let current = config.rotation.spawn(Tetromino::I, config.width);
+        let board = Board::new(config.width, config.height, config.hidden_rows);
+        let randomizer = config.randomizer.clone();
         let mut g = Self {
-            board: vec![0; (BOARD_W * BOARD_H) as usize],
-            current: Piece {
-                kind: Tetromino::I,
-                rot: 0,
-                x: 3,
-                y: 0,
-            },
-            next: Tetromino::I,
-            rng: StdRng::seed_from_u64(seed),
+            config,
+            board,
+            current,
+            queue: VecDeque::new(),
+            held: None,
+            hold_used: false,
+            initial_rotation: None,
+            initial_hold: false,
+            pieces_spawned: 0,
+            lock_timer: None,
+            lock_resets: 0,
+            lowest_y: 0,
+            fall_progress: 0.0,
+            last_kick: None,
+            delay: None,
+            randomizer,
+            seed,
+            rng: ChaCha12Rng::seed_from_u64(seed),
             score: 0,
             lines: 0,
-            game_over: false,
+            combo: None,
+            back_to_back: false,
+            game_over: None,
+            events: Vec::new(),
         };
 
-        g.next = g.random_piece();
+        g.fill_queue();
         g.spawn_new_piece();
         g
     }
 
+    /// Saves the complete game state, RNG included, as JSON.
+    #[cfg(feature = "serde")]
+    pub fn save_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Resumes a game saved with `save_json`; it plays on exactly as the
+    /// original would have.
+    #[cfg(feature = "serde")]
+    pub fn load_json(json: &str) -> Result<Self, serde_json::Error> {
+        let game: Self = serde_json::from_str(json)?;
+        game.check_loaded().map_err(serde::de::Error::custom)?;
+        Ok(game)
+    }
+
+    // A save can be well-formed JSON and still break what the game relies on:
+    // the limits `with_config_and_seed` clamps to, a board of the configured
+    // size and a full next queue.
+    #[cfg(feature = "serde")]
+    fn check_loaded(&self) -> Result<(), &'static str> {
+        let c = &self.config;
+        if !(4..=MAX_BOARD_SIZE).contains(&c.width) || !(4..=MAX_BOARD_SIZE).contains(&c.height) {
+            return Err("board size out of range");
+        }
+        if !(spawn_rows(&*c.rotation, c.width)..=MAX_BOARD_SIZE).contains(&c.hidden_rows) {
+            return Err("hidden rows out of range");
+        }
+        if !(1..=MAX_PREVIEW).contains(&c.preview) {
+            return Err("preview out of range");
+        }
+        if c.start_level > MAX_START_LEVEL {
+            return Err("start level out of range");
+        }
+        let b = &self.board;
+        let cells = (c.width * (c.height + c.hidden_rows)) as usize;
+        if (b.width(), b.height(), b.hidden_rows()) != (c.width, c.height, c.hidden_rows)
+            || b.cells().len() != cells
+        {
+            return Err("board does not match the config");
+        }
+        if self.queue.len() != c.preview {
+            return Err("next queue does not match the preview");
+        }
+        Ok(())
+    }
+
+    /// Copies the current state, RNG and randomizer included.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.clone(),
+            current: self.current,
+            queue: self.queue.clone(),
+            held: self.held,
+            hold_used: self.hold_used,
+            initial_rotation: self.initial_rotation,
+            initial_hold: self.initial_hold,
+            pieces_spawned: self.pieces_spawned,
+            lock_timer: self.lock_timer,
+            lock_resets: self.lock_resets,
+            lowest_y: self.lowest_y,
+            fall_progress: self.fall_progress,
+            last_kick: self.last_kick,
+            delay: self.delay.clone(),
+            randomizer: self.randomizer.clone(),
+            seed: self.seed,
+            rng: self.rng.clone(),
+            score: self.score,
+            lines: self.lines,
+            combo: self.combo,
+            back_to_back: self.back_to_back,
+            game_over: self.game_over,
+        }
+    }
+
+    /// Goes back to `snapshot`, which must come from a game with the same
+    /// config. Events not yet drained are kept.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.board.clone_from(&snapshot.board);
+        self.current = snapshot.current;
+        self.queue.clone_from(&snapshot.queue);
+        self.held = snapshot.held;
+        self.hold_used = snapshot.hold_used;
+        self.initial_rotation = snapshot.initial_rotation;
+        self.initial_hold = snapshot.initial_hold;
+        self.pieces_spawned = snapshot.pieces_spawned;
+        self.lock_timer = snapshot.lock_timer;
+        self.lock_resets = snapshot.lock_resets;
+        self.lowest_y = snapshot.lowest_y;
+        self.fall_progress = snapshot.fall_progress;
+        self.last_kick = snapshot.last_kick;
+        self.delay.clone_from(&snapshot.delay);
+        self.randomizer.clone_from(&snapshot.randomizer);
+        self.seed = snapshot.seed;
+        self.rng.clone_from(&snapshot.rng);
+        self.score = snapshot.score;
+        self.lines = snapshot.lines;
+        self.combo = snapshot.combo;
+        self.back_to_back = snapshot.back_to_back;
+        self.game_over = snapshot.game_over;
+    }
+
+    /// Starts over with a fresh random seed.
     pub fn reset(&mut self) {
-        self.board.fill(0);
+        self.reset_with_seed(rand::random());
+    }
+
+    /// Starts over with `seed`; pass `self.seed()` to replay the same sequence.
+    pub fn reset_with_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self.board.clear();
         self.score = 0;
         self.lines = 0;
-        self.game_over = false;
-        self.next = self.random_piece();
+        self.combo = None;
+        self.back_to_back = false;
+        self.game_over = None;
+        self.events.clear();
+        self.delay = None;
+        self.held = None;
+        self.initial_rotation = None;
+        self.initial_hold = false;
+        self.pieces_spawned = 0;
+        self.randomizer = self.config.randomizer.clone();
+        self.queue.clear();
+        self.fill_queue();
         self.spawn_new_piece();
     }
 
+    /// The seed this game's RNG was started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Same as `visible_board`.
     pub fn board(&self) -> &[Cell] {
-        &self.board
+        self.board.visible_cells()
+    }
+
+    /// The `width * height` visible cells, row by row.
+    pub fn visible_board(&self) -> &[Cell] {
+        self.board.visible_cells()
+    }
+
+    /// Every cell including the vanishing zone, which comes first.
+    pub fn full_board(&self) -> &[Cell] {
+        self.board.cells()
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// The whole board, vanishing zone included, as a [`BitBoard`] for
+    /// searches, or `None` if it is wider than [`bitboard::MAX_WIDTH`].
+    pub fn bit_board(&self) -> Option<BitBoard> {
+        (self.board.width() <= bitboard::MAX_WIDTH).then(|| BitBoard::from(&self.board))
+    }
+
+    pub fn width(&self) -> i32 {
+        self.board.width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.board.height()
+    }
+
+    pub fn hidden_rows(&self) -> i32 {
+        self.board.hidden_rows()
+    }
+
+    /// How many pieces have spawned since the game started, counting the
+    /// ones brought in by hold.
+    pub fn pieces_spawned(&self) -> u32 {
+        self.pieces_spawned
     }
 
     pub fn current_piece(&self) -> Piece {
         self.current
     }
 
+    pub fn held_piece(&self) -> Option<Tetromino> {
+        self.held
+    }
+
+    /// Whether `hold` would do anything right now.
+    pub fn can_hold(&self) -> bool {
+        self.in_play() && !self.hold_used
+    }
+
+    /// Takes every event recorded since the last call, oldest first.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Upcoming pieces, soonest first.
+    pub fn next_queue(&self) -> impl Iterator<Item = Tetromino> + '_ {
+        self.queue.iter().copied()
+    }
+
+    pub fn rotation_system(&self) -> &dyn RotationSystem {
+        self.config.rotation.as_ref()
+    }
+
+    /// Board cells covered by `piece` under this game's rotation system.
+    pub fn blocks(&self, piece: Piece) -> [(i32, i32); 4] {
+        self.config.rotation.blocks(piece)
+    }
+
     pub fn ghost_piece(&self) -> Piece {
         // Project the current piece down until it would collide.
         let mut p = self.current;
@@ -185,118 +649,322 @@ impl Game {
         self.lines
     }
 
+    /// Consecutive line clears minus one, `None` when no run is going.
+    pub fn combo(&self) -> Option<u32> {
+        self.combo
+    }
+
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
     pub fn level(&self) -> u32 {
-        (self.lines / 10) + 1
+        self.config
+            .start_level
+            .saturating_add(self.lines / self.config.lines_per_level.max(1))
+    }
+
+    /// Current gravity in G, from the configured curve.
+    pub fn gravity(&self) -> f64 {
+        self.config.gravity.gravity(self.level())
     }
 
     pub fn is_game_over(&self) -> bool {
+        self.game_over.is_some()
+    }
+
+    pub fn game_over_reason(&self) -> Option<GameOverReason> {
         self.game_over
     }
 
     pub fn cell(&self, x: i32, y: i32) -> Cell {
-        if x < 0 || x >= BOARD_W || y < 0 || y >= BOARD_H {
-            return 0;
+        self.board.get(x, y)
+    }
+
+    /// What the game is doing right now. During `LineClear` and `Are` the
+    /// current piece is the one that just locked and is no longer in play.
+    pub fn phase(&self) -> Phase {
+        if self.is_game_over() {
+            return Phase::GameOver;
+        }
+        match &self.delay {
+            Some(Delay::LineClear { rows, remaining }) => Phase::LineClear {
+                rows: rows.clone(),
+                remaining: *remaining,
+            },
+            Some(Delay::Are { remaining }) => Phase::Are {
+                remaining: *remaining,
+            },
+            None if self.lock_timer.is_some() => Phase::LockDelay,
+            None => Phase::Falling,
         }
-        self.board[(y * BOARD_W + x) as usize]
     }
 
+    /// One manual gravity step: the piece falls a row, or, when it is on the
+    /// ground, its lock delay runs for as long as one row takes to fall at
+    /// the current gravity. Between pieces the line clear delay and ARE run
+    /// down by the same amount, returning `Waiting`. Frontends that never
+    /// call `update` can drive a whole game with it.
     pub fn tick(&mut self) -> Step {
-        if self.game_over {
+        let frames = 1.0 / self.gravity();
+        let elapsed =
+            Duration::try_from_secs_f64(frames / FRAMES_PER_SECOND).unwrap_or(Duration::MAX);
+        if self.delay.is_some() && !self.is_game_over() {
+            return self.run_delay(elapsed).unwrap_or(Step::Waiting);
+        }
+        let step = self.fall();
+        if step != Step::Grounded {
+            return step;
+        }
+        if let Some(timer) = self.lock_timer.as_mut() {
+            *timer = timer.saturating_add(elapsed);
+        }
+        if self.lock_expired() {
+            self.lock_and_spawn()
+        } else {
+            Step::Grounded
+        }
+    }
+
+    // Moves the piece down a row, or locks it if its lock delay is up.
+    fn fall(&mut self) -> Step {
+        if self.is_game_over() {
             return Step::GameOver;
         }
+        if self.delay.is_some() {
+            return Step::Waiting;
+        }
 
         if self.try_move(0, 1) {
             return Step::Moved;
         }
 
-        self.lock_piece();
-        let cleared = self.clear_lines();
-        self.apply_score(cleared);
-        self.spawn_new_piece();
+        if self.lock_expired() {
+            self.lock_and_spawn()
+        } else {
+            Step::Grounded
+        }
+    }
 
-        Step::Locked {
-            cleared,
-            game_over: self.game_over,
+    /// Advances the game clock by `dt`: applies gravity for the current level
+    /// and runs the lock delay of a grounded piece, or the line clear delay
+    /// and ARE between pieces. Returns `Moved` if the piece fell, `Locked` if
+    /// it locked, `GameOver` if the next piece could not spawn after a delay,
+    /// and `None` if nothing happened.
+    pub fn update(&mut self, dt: Duration) -> Option<Step> {
+        if self.is_game_over() {
+            return None;
+        }
+        if self.delay.is_some() {
+            return self.run_delay(dt);
+        }
+
+        if let Some(timer) = self.lock_timer.as_mut() {
+            *timer = timer.saturating_add(dt);
+            return self.lock_expired().then(|| self.lock_and_spawn());
+        }
+
+        let gravity = self.gravity();
+        let mut moved = false;
+        if gravity >= MAX_GRAVITY {
+            moved = self.drop_to_floor() > 0;
+        } else {
+            self.fall_progress += gravity * FRAMES_PER_SECOND * dt.as_secs_f64();
+            while self.fall_progress >= 1.0 {
+                self.fall_progress -= 1.0;
+                if !self.try_move(0, 1) {
+                    break;
+                }
+                moved = true;
+            }
         }
+        if self.lock_timer.is_some() {
+            // Landed; leftover progress must not carry over to the next piece.
+            self.fall_progress = 0.0;
+        }
+        moved.then_some(Step::Moved)
+    }
+
+    /// Runs `command`. Drops and ticks return the step they caused.
+    pub fn apply(&mut self, command: Command) -> Option<Step> {
+        match command {
+            Command::Left => self.move_left(),
+            Command::Right => self.move_right(),
+            Command::RotateCw => self.rotate_cw(),
+            Command::RotateCcw => self.rotate_ccw(),
+            Command::Rotate180 => self.rotate_180(),
+            Command::SoftDrop => return Some(self.soft_drop()),
+            Command::HardDrop => return Some(self.hard_drop()),
+            Command::Hold => self.hold(),
+            Command::Tick => return Some(self.tick()),
+        }
+        None
     }
 
     pub fn move_left(&mut self) {
-        if !self.game_over {
+        if self.in_play() {
             self.try_move(-1, 0);
         }
     }
 
     pub fn move_right(&mut self) {
-        if !self.game_over {
+        if self.in_play() {
             self.try_move(1, 0);
         }
     }
 
     pub fn soft_drop(&mut self) -> Step {
-        self.tick()
+        let step = self.fall();
+        if step == Step::Moved {
+            self.add_score(self.config.scoring.soft_drop_points(1));
+        }
+        step
     }
 
     pub fn hard_drop(&mut self) -> Step {
-        if self.game_over {
+        if self.is_game_over() {
             return Step::GameOver;
         }
+        if self.delay.is_some() {
+            return Step::Waiting;
+        }
 
-        while self.try_move(0, 1) {}
-        self.lock_piece();
-        let cleared = self.clear_lines();
-        self.apply_score(cleared);
-        self.spawn_new_piece();
+        let cells = self.drop_to_floor();
+        self.add_score(self.config.scoring.hard_drop_points(cells as u32));
+        self.lock_and_spawn()
+    }
 
-        Step::Locked {
-            cleared,
-            game_over: self.game_over,
+    /// Swaps the current piece into the hold slot. The outgoing piece comes
+    /// back from hold, or from the queue when hold was empty, at its spawn
+    /// position. Only allowed once per piece until it locks.
+    pub fn hold(&mut self) {
+        if !self.can_hold() {
+            return;
         }
+        self.emit(Event::Hold(self.current.kind));
+        // Not `spawn_new_piece`: a pending IHS is for the next piece off the
+        // queue, not this swap.
+        let kind = match self.held.replace(self.current.kind) {
+            Some(kind) => kind,
+            None => self.take_from_queue(),
+        };
+        self.spawn_piece(kind);
+        self.hold_used = true;
+    }
+
+    /// Initial Rotation System: the next piece to spawn starts out turned,
+    /// if it fits that way. Cleared once a piece spawns.
+    pub fn set_initial_rotation(&mut self, turn: Option<Turn>) {
+        self.initial_rotation = turn;
+    }
+
+    /// Initial Hold System: the next piece goes straight into hold as it
+    /// spawns. Cleared once a piece spawns from the queue.
+    pub fn set_initial_hold(&mut self, hold: bool) {
+        self.initial_hold = hold;
     }
-    // Todo
+
     pub fn rotate_cw(&mut self) {
-        if self.game_over {
+        self.rotate(1);
+    }
+
+    pub fn rotate_ccw(&mut self) {
+        self.rotate(3);
+    }
+
+    pub fn rotate_180(&mut self) {
+        self.rotate(2);
+    }
+
+    fn rotate(&mut self, turns: u8) {
+        if !self.in_play() {
             return;
         }
-        let mut rotated = self.current;
-        rotated.rot = (rotated.rot + 1) % 4;
-
-        // Small "wall kick" offsets
-        const KICKS: [i32; 5] = [0, -1, 1, -2, 2];
-        for dx in KICKS {
-            let mut candidate = rotated;
-            candidate.x += dx;
-            if self.is_valid(candidate) {
-                self.current = candidate;
-                break;
-            }
+        let occupied = |x, y| self.occupied(x, y);
+        if let Some(rotated) = self.config.rotation.rotate(self.current, turns, &occupied) {
+            let kick = (rotated.x - self.current.x, rotated.y - self.current.y);
+            self.place(rotated);
+            self.last_kick = Some(kick);
+            self.emit(Event::PieceRotated(rotated));
+            self.apply_max_gravity();
         }
     }
 
     fn random_piece(&mut self) -> Tetromino {
-        match self.rng.random_range(0..7) {
-            0 => Tetromino::I,
-            1 => Tetromino::O,
-            2 => Tetromino::T,
-            3 => Tetromino::S,
-            4 => Tetromino::Z,
-            5 => Tetromino::J,
-            _ => Tetromino::L,
+        self.randomizer.next(&mut self.rng)
+    }
+
+    fn fill_queue(&mut self) {
+        while self.queue.len() < self.config.preview {
+            let piece = self.random_piece();
+            self.queue.push_back(piece);
         }
     }
 
     fn spawn_new_piece(&mut self) {
-        let kind = self.next;
-        self.next = self.random_piece();
+        let mut kind = self.take_from_queue();
+        self.hold_used = false;
+        if std::mem::take(&mut self.initial_hold) {
+            self.emit(Event::Hold(kind));
+            kind = match self.held.replace(kind) {
+                Some(held) => held,
+                None => self.take_from_queue(),
+            };
+            self.hold_used = true;
+        }
+        self.spawn_piece(kind);
+    }
 
-        self.current = Piece {
-            kind,
-            rot: 0,
-            x: 3,
-            y: 0,
-        };
+    fn take_from_queue(&mut self) -> Tetromino {
+        // The queue is kept full, so there is always a piece to take.
+        let kind = self.queue.pop_front().unwrap_or(Tetromino::I);
+        self.fill_queue();
+        kind
+    }
 
-        if !self.is_valid(self.current) {
-            self.game_over = true;
+    fn spawn_piece(&mut self, kind: Tetromino) {
+        let mut piece = self.config.rotation.spawn(kind, self.board.width());
+        if let Some(turn) = self.initial_rotation.take() {
+            let rotated = Piece {
+                rot: (piece.rot + turn.quarter_turns()) % 4,
+                ..piece
+            };
+            if self.is_valid(rotated) {
+                piece = rotated;
+            }
+        }
+        if self.config.spawn_drop {
+            let mut below = piece;
+            below.y += 1;
+            if self.is_valid(piece) && self.is_valid(below) {
+                piece = below;
+            }
+        }
+        self.current = piece;
+        self.pieces_spawned += 1;
+        self.lock_timer = None;
+        self.lock_resets = 0;
+        self.lowest_y = self.current.y;
+        self.fall_progress = 0.0;
+        self.last_kick = None;
+        self.update_grounded();
+
+        if self.is_valid(self.current) {
+            self.emit(Event::PieceSpawned(self.current));
+            self.apply_max_gravity();
+        } else {
+            self.end_game(GameOverReason::BlockOut);
+        }
+    }
+
+    fn end_game(&mut self, reason: GameOverReason) {
+        self.game_over = Some(reason);
+        self.emit(Event::GameOver(reason));
+    }
+
+    fn emit(&mut self, event: Event) {
+        if self.config.record_events {
+            self.events.push(event);
         }
     }
 
@@ -305,86 +973,238 @@ impl Game {
         moved.x += dx;
         moved.y += dy;
         if self.is_valid(moved) {
-            self.current = moved;
+            self.place(moved);
+            self.emit(Event::PieceMoved(moved));
+            self.apply_max_gravity();
             true
         } else {
             false
         }
     }
-    // Todo
-    fn is_valid(&self, piece: Piece) -> bool {
-        for (x, y) in blocks_for(piece) {
-            if x < 0 || x >= BOARD_W || y >= BOARD_H {
-                return false;
-            }
-            if y >= 0 {
-                let idx = (y * BOARD_W + x) as usize;
-                if self.board[idx] != 0 {
-                    return false;
+
+    // Drops the current piece onto the stack in one go, returning the rows fallen.
+    fn drop_to_floor(&mut self) -> i32 {
+        let target = self.ghost_piece();
+        let cells = target.y - self.current.y;
+        if cells > 0 {
+            self.place(target);
+            self.emit(Event::PieceMoved(target));
+        }
+        cells
+    }
+
+    // At 20G a piece never hangs in the air, so it lands as soon as it
+    // spawns or moves instead of on the next update.
+    fn apply_max_gravity(&mut self) {
+        if self.gravity() >= MAX_GRAVITY {
+            self.drop_to_floor();
+        }
+    }
+
+    // Moves the current piece to an already validated spot and keeps the lock
+    // delay bookkeeping in step with it.
+    fn place(&mut self, piece: Piece) {
+        let reset = self.config.lock_delay.reset;
+        let fell = piece.y > self.lowest_y;
+        let shifted = piece.x != self.current.x || piece.rot != self.current.rot;
+        self.current = piece;
+        self.last_kick = None;
+
+        if fell {
+            self.lowest_y = piece.y;
+            self.lock_resets = 0;
+            self.lock_timer = None;
+        } else if shifted && self.lock_timer.is_some() {
+            match reset {
+                LockReset::Move { limit } if self.lock_resets < limit => {
+                    self.lock_resets += 1;
+                    self.lock_timer = Some(Duration::ZERO);
                 }
+                LockReset::Infinite => self.lock_timer = Some(Duration::ZERO),
+                _ => {}
             }
         }
-        true
+        self.update_grounded();
     }
 
-    // Todo
-    fn lock_piece(&mut self) {
-        let id = self.current.kind.id();
-        for (x, y) in blocks_for(self.current) {
-            if y < 0 {
-                continue;
+    // Starts the lock timer when the piece touches down and stops it when the
+    // piece is lifted off again (moved over a gap or kicked upwards).
+    fn update_grounded(&mut self) {
+        let mut below = self.current;
+        below.y += 1;
+        if self.is_valid(below) {
+            self.lock_timer = None;
+        } else if self.lock_timer.is_none() {
+            let exhausted = match self.config.lock_delay.reset {
+                LockReset::Move { limit } => self.lock_resets >= limit,
+                _ => false,
+            };
+            // Out of resets: the piece locks as soon as it lands again.
+            self.lock_timer = Some(if exhausted {
+                self.config.lock_delay.duration
+            } else {
+                Duration::ZERO
+            });
+        }
+    }
+
+    fn lock_expired(&self) -> bool {
+        self.lock_timer
+            .is_some_and(|t| t >= self.config.lock_delay.duration)
+    }
+
+    fn lock_and_spawn(&mut self) -> Step {
+        let piece = self.current;
+        let spin = self.t_spin();
+        let level = self.level();
+        let above = self.blocks(piece).iter().filter(|&&(_, y)| y < 0).count();
+        let rules = self.config.top_out;
+        let top_out = match above {
+            4 if rules.lock_out => Some(GameOverReason::LockOut),
+            n if n > 0 && rules.partial_lock_out => Some(GameOverReason::PartialLockOut),
+            _ => None,
+        };
+        self.lock_piece();
+        let rows = self.board.full_rows();
+        let cleared = rows.len() as u32;
+        // Whatever is not cleared is empty; the rows may not be gone yet.
+        let perfect_clear = cleared > 0
+            && self
+                .board
+                .cells()
+                .chunks(self.board.width() as usize)
+                .all(|row| row.iter().all(|&c| c != 0) || row.iter().all(|&c| c == 0));
+        self.lines += cleared;
+        let (points, back_to_back) = self.apply_score(cleared, spin, perfect_clear, level);
+        let clear_delay = self.config.line_clear_delay;
+        if cleared > 0 && (clear_delay.is_zero() || top_out.is_some()) {
+            self.board.clear_lines();
+        }
+        // Lock events must come before the spawn events, but need `game_over`.
+        let mark = self.events.len();
+        match top_out {
+            Some(reason) => self.end_game(reason),
+            None if cleared > 0 && !clear_delay.is_zero() => {
+                self.delay = Some(Delay::LineClear {
+                    rows: rows.clone(),
+                    remaining: clear_delay,
+                });
             }
-            let idx = (y * BOARD_W + x) as usize;
-            self.board[idx] = id;
-        }
-    }
-    // Todo
-    fn clear_lines(&mut self) -> u32 {
-        let mut cleared = 0u32;
-        let mut y = BOARD_H - 1;
-        while y >= 0 {
-            let mut full = true;
-            for x in 0..BOARD_W {
-                if self.board[(y * BOARD_W + x) as usize] == 0 {
-                    full = false;
-                    break;
-                }
+            None => self.start_are(),
+        }
+
+        let event = LockEvent {
+            piece,
+            rows,
+            spin,
+            combo: self.combo,
+            back_to_back,
+            points,
+            perfect_clear,
+            level_up: self.level() > level,
+            game_over: self.is_game_over(),
+        };
+        if self.config.record_events {
+            let mut events = vec![Event::PieceLocked(event.clone())];
+            if !event.rows.is_empty() {
+                events.push(Event::LinesCleared(event.rows.clone()));
             }
+            if event.level_up {
+                events.push(Event::LevelUp(self.level()));
+            }
+            self.events.splice(mark..mark, events);
+        }
+        Step::Locked(event)
+    }
 
-            if full {
-                cleared += 1;
-                // Move all rows [0..y) down by one.
-                for yy in (1..=y).rev() {
-                    for x in 0..BOARD_W {
-                        let from = ((yy - 1) * BOARD_W + x) as usize;
-                        let to = (yy * BOARD_W + x) as usize;
-                        self.board[to] = self.board[from];
-                    }
-                }
-                // Clear top row.
-                for x in 0..BOARD_W {
-                    self.board[x as usize] = 0;
-                }
-                // Stay on same y to check the shifted row.
+    fn start_are(&mut self) {
+        let are = self.config.are;
+        if are.is_zero() {
+            self.spawn_new_piece();
+        } else {
+            self.delay = Some(Delay::Are { remaining: are });
+        }
+    }
+
+    // Runs down the line clear delay and ARE; leftover time carries into
+    // the next delay but not past the spawn.
+    fn run_delay(&mut self, mut dt: Duration) -> Option<Step> {
+        while let Some(delay) = self.delay.as_mut() {
+            let (Delay::LineClear { remaining, .. } | Delay::Are { remaining }) = delay;
+            if *remaining > dt {
+                *remaining -= dt;
+                return None;
+            }
+            dt -= *remaining;
+            if let Some(Delay::LineClear { .. }) = self.delay.take() {
+                self.board.clear_lines();
+                self.start_are();
             } else {
-                y -= 1;
+                self.spawn_new_piece();
             }
         }
+        self.is_game_over().then_some(Step::GameOver)
+    }
 
-        self.lines += cleared;
-        cleared
+    fn in_play(&self) -> bool {
+        !self.is_game_over() && self.delay.is_none()
+    }
+
+    // Only meaningful right before the current piece locks.
+    fn t_spin(&self) -> TSpin {
+        match self.last_kick {
+            Some(kick) if self.current.kind == Tetromino::T => {
+                let occupied = |x, y| self.occupied(x, y);
+                spin::detect(self.blocks(self.current), kick, &occupied)
+            }
+            _ => TSpin::None,
+        }
     }
 
-    fn apply_score(&mut self, cleared: u32) {
-        let lvl = self.level();
-        let add = match cleared {
-            1 => 40,
-            2 => 100,
-            3 => 300,
-            4 => 1200,
-            _ => 0,
+    // Walls, floor and the ceiling above the vanishing zone count as
+    // occupied, so no block can lock where the board cannot keep it.
+    fn occupied(&self, x: i32, y: i32) -> bool {
+        if !self.board.in_bounds(x, y) {
+            return true;
+        }
+        self.board.get(x, y) != 0
+    }
+
+    fn is_valid(&self, piece: Piece) -> bool {
+        let occupied = |x, y| self.occupied(x, y);
+        self.config.rotation.fits(piece, &occupied)
+    }
+
+    fn lock_piece(&mut self) {
+        let id = self.current.kind.id();
+        for (x, y) in self.blocks(self.current) {
+            self.board.set(x, y, id);
+        }
+    }
+
+    // Returns the points awarded and whether back-to-back applied.
+    fn apply_score(&mut self, cleared: u32, spin: TSpin, perfect: bool, level: u32) -> (u32, bool) {
+        let difficult = scoring::is_difficult(cleared, spin);
+        let chained = difficult && self.back_to_back;
+        self.combo = if cleared > 0 {
+            Some(self.combo.map_or(0, |c| c + 1))
+        } else {
+            None
         };
-        self.score = self.score.saturating_add(add * lvl);
+        // Spins without lines neither extend nor break back-to-back.
+        if cleared > 0 {
+            self.back_to_back = difficult;
+        }
+        let points = self
+            .config
+            .scoring
+            .lock_points(cleared, spin, perfect, level, chained, self.combo);
+        self.add_score(points);
+        (points, chained)
+    }
+
+    fn add_score(&mut self, points: u32) {
+        self.score = self.score.saturating_add(points);
     }
 }
 
@@ -396,15 +1216,675 @@ mod tests {
     fn new_game_has_empty_board() {
         let g = Game::new();
         assert_eq!(g.board().len(), (BOARD_W * BOARD_H) as usize);
-        assert!(g.board().iter().any(|&c| c == 0));
+        assert!(g.board().contains(&0));
     }
 
     #[test]
     fn piece_blocks_in_bounds_on_spawn() {
         let g = Game::new();
-        for (x, y) in blocks_for(g.current_piece()) {
-            assert!(x >= 0 && x < BOARD_W);
-            assert!(y >= 0 && y < BOARD_H);
+        for (x, y) in g.blocks(g.current_piece()) {
+            assert!((0..BOARD_W).contains(&x));
+            assert!((-g.hidden_rows()..BOARD_H).contains(&y));
+        }
+    }
+
+    #[test]
+    fn next_queue_feeds_spawns_in_order() {
+        let mut g = Game::with_config(GameConfig {
+            preview: 3,
+            ..GameConfig::default()
+        });
+        let upcoming: Vec<_> = g.next_queue().collect();
+        assert_eq!(upcoming.len(), 3);
+        g.hard_drop();
+        assert_eq!(g.current_piece().kind, upcoming[0]);
+        assert_eq!(g.next_queue().take(2).collect::<Vec<_>>(), upcoming[1..]);
+        assert_eq!(g.next_queue().count(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn saved_game_resumes_identically() {
+        let mut g = Game::with_seed(11);
+        for command in [
+            Command::Left,
+            Command::HardDrop,
+            Command::Hold,
+            Command::HardDrop,
+        ] {
+            g.apply(command);
+        }
+        g.update(ms(200));
+
+        let mut loaded = Game::load_json(&g.save_json().unwrap()).unwrap();
+        for command in [Command::RotateCw, Command::HardDrop, Command::Hold] {
+            g.apply(command);
+            loaded.apply(command);
+        }
+        for _ in 0..50 {
+            g.hard_drop();
+            loaded.hard_drop();
+        }
+        assert_eq!(loaded.save_json().unwrap(), g.save_json().unwrap());
+        assert_eq!(loaded.full_board(), g.full_board());
+        assert_eq!(loaded.score(), g.score());
+
+        // Gravity above level 1 leaves arbitrary fractions of a row behind,
+        // which must survive the round trip exactly.
+        for start_level in [5, 12] {
+            let mut g = Game::with_config_and_seed(
+                GameConfig {
+                    start_level,
+                    ..GameConfig::default()
+                },
+                3,
+            );
+            for frame in 0..300u64 {
+                g.update(Duration::from_micros(16_667 + frame % 7));
+                let loaded = Game::load_json(&g.save_json().unwrap()).unwrap();
+                assert_eq!(loaded.fall_progress.to_bits(), g.fall_progress.to_bits());
+                g = loaded;
+                if frame % 40 == 39 {
+                    g.hard_drop();
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn load_json_rejects_broken_saves() {
+        let save: serde_json::Value =
+            serde_json::from_str(&Game::with_seed(11).save_json().unwrap()).unwrap();
+        let broken = |edit: fn(&mut serde_json::Value)| {
+            let mut save = save.clone();
+            edit(&mut save);
+            Game::load_json(&save.to_string()).unwrap_err().to_string()
+        };
+        assert!(
+            broken(|s| {
+                s["board"]["cells"].as_array_mut().unwrap().pop();
+            })
+            .contains("board does not match")
+        );
+        assert!(
+            broken(|s| {
+                s["config"]["preview"] = 0.into();
+                s["queue"] = serde_json::json!([]);
+            })
+            .contains("preview out of range")
+        );
+        assert!(
+            broken(|s| {
+                s["queue"].as_array_mut().unwrap().clear();
+            })
+            .contains("next queue")
+        );
+        assert!(broken(|s| s["config"]["width"] = 100_000.into()).contains("board size"));
+    }
+
+    #[test]
+    fn hold_swaps_once_per_piece() {
+        let mut g = Game::new();
+        let first = g.current_piece().kind;
+        let second = g.next_queue().next().unwrap();
+        g.move_left();
+        g.hold();
+        assert_eq!(g.held_piece(), Some(first));
+        assert_eq!(g.current_piece().kind, second);
+        assert!(!g.can_hold());
+
+        g.hold();
+        assert_eq!(g.current_piece().kind, second);
+
+        g.hard_drop();
+        assert!(g.can_hold());
+        let third = g.current_piece().kind;
+        g.hold();
+        assert_eq!(g.held_piece(), Some(third));
+        let p = g.current_piece();
+        assert_eq!(p.kind, first);
+        // Guideline spawn above the field, then the spawn drop.
+        assert_eq!((p.x, p.y, p.rot), (3, -1, 0));
+    }
+
+    #[test]
+    fn same_seed_deals_same_pieces() {
+        let mut a = Game::with_seed(42);
+        let mut b = Game::with_seed(42);
+        assert_eq!(a.seed(), 42);
+        for _ in 0..20 {
+            assert_eq!(a.current_piece().kind, b.current_piece().kind);
+            a.hard_drop();
+            b.hard_drop();
+        }
+
+        let first: Vec<_> = Game::with_seed(42).next_queue().collect();
+        a.reset_with_seed(a.seed());
+        assert_eq!(a.next_queue().collect::<Vec<_>>(), first);
+    }
+
+    #[test]
+    fn t_spin_double_is_full() {
+        let mut g = game_with(Tetromino::T, 1, 1, BOARD_H - 3);
+        fill(&mut g, &["...#......", "#...######", "##.#######"]);
+        g.rotate_cw();
+        assert_eq!(g.t_spin(), TSpin::Full);
+        let Step::Locked(event) = g.hard_drop() else {
+            panic!("piece did not lock");
+        };
+        assert_eq!(event.rows, [BOARD_H - 2, BOARD_H - 1]);
+        assert_eq!(event.spin, TSpin::Full);
+        assert_eq!(event.points, 1200);
+        assert!(!event.perfect_clear && !event.back_to_back);
+    }
+
+    #[test]
+    fn t_spin_mini_is_cancelled_by_moves() {
+        // Both back corners filled, but only one in front of the nub.
+        let mut g = game_with(Tetromino::T, 1, 0, BOARD_H - 3);
+        fill(&mut g, &["#.#.......", "..........", "#........."]);
+        g.rotate_cw();
+        assert_eq!(g.t_spin(), TSpin::Mini);
+        g.move_right();
+        g.move_left();
+        assert_eq!(g.t_spin(), TSpin::None);
+    }
+
+    #[test]
+    fn lock_event_reports_split_clears() {
+        let mut g = game_with(Tetromino::I, 1, 3, BOARD_H - 4);
+        g.config.lines_per_level = 2;
+        fill(
+            &mut g,
+            &["#####.####", "##.##.####", "#####.####", "#####.####"],
+        );
+        let Step::Locked(event) = g.hard_drop() else {
+            panic!("piece did not lock");
+        };
+        assert_eq!(event.piece.x, 3);
+        assert_eq!(event.rows, [BOARD_H - 4, BOARD_H - 2, BOARD_H - 1]);
+        assert_eq!(event.cleared(), 3);
+        assert!(event.level_up);
+        assert!(!event.perfect_clear);
+        assert_eq!(g.cell(0, BOARD_H - 1), 1);
+    }
+
+    #[test]
+    fn events_follow_the_piece_lifecycle() {
+        let mut g = game_with(Tetromino::O, 0, 3, BOARD_H - 4);
+        g.config.record_events = true;
+        fill(&mut g, &["####..####"]);
+
+        g.move_left();
+        g.move_right();
+        g.rotate_cw();
+        g.hard_drop();
+        let events: Vec<_> = g.drain_events().collect();
+        assert!(matches!(
+            events[..],
+            [
+                Event::PieceMoved(_),
+                Event::PieceMoved(_),
+                Event::PieceRotated(_),
+                Event::PieceMoved(Piece { y: 18, .. }),
+                Event::PieceLocked(_),
+                Event::LinesCleared(_),
+                Event::PieceSpawned(_),
+            ]
+        ));
+
+        g.hold();
+        let events: Vec<_> = g.drain_events().collect();
+        assert!(matches!(
+            events[..],
+            [Event::Hold(_), Event::PieceSpawned(_)]
+        ));
+        assert_eq!(g.drain_events().count(), 0);
+    }
+
+    #[test]
+    fn events_are_off_by_default() {
+        let mut g = Game::with_seed(3);
+        g.hard_drop();
+        assert_eq!(g.drain_events().count(), 0);
+    }
+
+    #[test]
+    fn guideline_scores_drops_and_combos() {
+        let mut g = game_with(Tetromino::O, 0, 3, BOARD_H - 4);
+        fill(&mut g, &["####..####", "####..####"]);
+        g.soft_drop();
+        g.hard_drop();
+        // 1 soft and 1 hard row, then a double that empties the board.
+        assert_eq!(g.score(), 1 + 2 + 300 + 1200);
+        assert_eq!(g.combo(), Some(0));
+        assert!(!g.back_to_back());
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn grounded_piece_locks_after_delay() {
+        let mut g = game_with(Tetromino::O, 0, 3, BOARD_H - 2);
+        assert_eq!(g.soft_drop(), Step::Grounded);
+        assert_eq!(g.update(ms(499)), None);
+        assert!(matches!(g.update(ms(1)), Some(Step::Locked(_))));
+        assert_eq!(g.cell(4, BOARD_H - 1), Tetromino::O.id());
+    }
+
+    #[test]
+    fn tick_alone_locks_grounded_pieces() {
+        let mut g = Game::with_seed(2);
+        let mut locks = 0;
+        for _ in 0..200 {
+            if let Step::Locked(_) = g.tick() {
+                locks += 1;
+            }
         }
+        assert!(locks >= 5, "only {locks} locks");
+
+        // At higher gravity a row takes less time, so the delay spans ticks.
+        let mut g = game_with(Tetromino::O, 0, 3, BOARD_H - 2);
+        g.config.start_level = 10;
+        while g.tick() == Step::Grounded {}
+        assert_eq!(g.cell(4, BOARD_H - 1), Tetromino::O.id());
+    }
+
+    #[test]
+    fn tick_alone_runs_delays_between_pieces() {
+        let config = GameConfig {
+            are: ms(100),
+            line_clear_delay: ms(400),
+            ..GameConfig::default()
+        };
+        let mut g = Game::with_config_and_seed(config, 2);
+        g.hard_drop();
+        assert_eq!(g.phase(), Phase::Are { remaining: ms(100) });
+        // A row takes a second at level 1, so one tick covers the whole ARE.
+        assert_eq!(g.tick(), Step::Waiting);
+        assert_eq!(g.phase(), Phase::Falling);
+
+        let mut locks = 0;
+        for _ in 0..400 {
+            if let Step::Locked(_) = g.tick() {
+                locks += 1;
+            }
+        }
+        assert!(locks >= 5, "only {locks} locks");
+    }
+
+    #[test]
+    fn huge_updates_lock_without_overflowing() {
+        let mut g = game_with(Tetromino::O, 0, 3, BOARD_H - 2);
+        assert_eq!(g.update(ms(1)), None);
+        assert!(matches!(g.update(Duration::MAX), Some(Step::Locked(_))));
+    }
+
+    #[test]
+    fn moves_reset_lock_delay_up_to_limit() {
+        let mut g = game_with(Tetromino::O, 0, 3, BOARD_H - 2);
+        g.config.lock_delay.reset = LockReset::Move { limit: 2 };
+        g.update(ms(400));
+        g.move_left();
+        g.update(ms(400));
+        g.move_right();
+        assert_eq!(g.update(ms(400)), None);
+        // Third move is over the limit and no longer buys time.
+        g.move_left();
+        assert!(matches!(g.update(ms(100)), Some(Step::Locked(_))));
+    }
+
+    #[test]
+    fn step_reset_ignores_moves() {
+        let mut g = game_with(Tetromino::O, 0, 3, BOARD_H - 2);
+        g.config.lock_delay.reset = LockReset::Step;
+        g.update(ms(400));
+        g.move_left();
+        assert!(matches!(g.update(ms(100)), Some(Step::Locked(_))));
+    }
+
+    #[test]
+    fn update_applies_sub_cell_gravity() {
+        let mut g = game_with(Tetromino::T, 0, 3, 0);
+        // Level 1 falls one row per second.
+        assert_eq!(g.update(ms(600)), None);
+        assert_eq!(g.update(ms(600)), Some(Step::Moved));
+        assert_eq!(g.current_piece().y, 1);
+    }
+
+    #[test]
+    fn twenty_g_drops_to_the_floor() {
+        let mut g = game_with(Tetromino::T, 0, 3, 0);
+        g.lines = 200;
+        assert_eq!(g.gravity(), MAX_GRAVITY);
+        assert_eq!(g.update(ms(16)), Some(Step::Moved));
+        assert_eq!(g.current_piece().y, g.ghost_piece().y);
+    }
+
+    #[test]
+    fn twenty_g_lands_on_spawn_and_after_moves() {
+        let mut g = Game::with_config(GameConfig {
+            start_level: 30,
+            ..GameConfig::default()
+        });
+        assert_eq!(g.gravity(), MAX_GRAVITY);
+        assert_eq!(g.current_piece(), g.ghost_piece());
+        g.hard_drop();
+        assert_eq!(g.current_piece(), g.ghost_piece());
+
+        // Sliding off a ledge lands on the floor below right away.
+        g.board = Board::new(BOARD_W, BOARD_H, 20);
+        fill(&mut g, &["####......"; 10]);
+        g.current = Piece {
+            kind: Tetromino::O,
+            rot: 0,
+            x: 0,
+            y: 0,
+        };
+        g.current = g.ghost_piece();
+        let ledge = g.current_piece().y;
+        for _ in 0..6 {
+            g.move_right();
+            assert_eq!(g.current_piece(), g.ghost_piece());
+        }
+        assert!(g.current_piece().y > ledge);
+    }
+
+    #[test]
+    fn board_size_comes_from_config() {
+        let mut g = Game::with_config(GameConfig {
+            width: 4,
+            height: 40,
+            ..GameConfig::default()
+        });
+        assert_eq!((g.width(), g.height()), (4, 40));
+        assert_eq!(g.board().len(), 160);
+        assert_eq!(g.current_piece().x, 0);
+
+        // A horizontal I fills a whole row on a 4-wide board.
+        g.current = Piece {
+            kind: Tetromino::I,
+            rot: 0,
+            x: 0,
+            y: 0,
+        };
+        let Step::Locked(event) = g.hard_drop() else {
+            panic!("piece did not lock");
+        };
+        assert_eq!(event.rows, [39]);
+        assert!(event.perfect_clear);
+    }
+
+    #[test]
+    fn bit_board_needs_a_narrow_board() {
+        let mut g = game_with(Tetromino::O, 0, 3, 0);
+        fill(&mut g, &["##.#######"]);
+        let bits = g.bit_board().unwrap();
+        assert_eq!(bits.colors(), g.full_board());
+
+        let wide = Game::with_config(GameConfig {
+            width: bitboard::MAX_WIDTH + 1,
+            ..GameConfig::default()
+        });
+        assert!(wide.bit_board().is_none());
+    }
+
+    #[test]
+    fn config_is_clamped_to_sane_limits() {
+        let g = Game::with_config(GameConfig {
+            width: i32::MAX,
+            height: 0,
+            hidden_rows: -5,
+            preview: usize::MAX,
+            start_level: u32::MAX,
+            ..GameConfig::default()
+        });
+        assert_eq!((g.width(), g.height()), (MAX_BOARD_SIZE, 4));
+        assert_eq!(g.config().hidden_rows, 2);
+        assert_eq!(g.next_queue().count(), MAX_PREVIEW);
+        assert_eq!(g.level(), MAX_START_LEVEL);
+    }
+
+    #[test]
+    fn hidden_rows_cover_the_spawn() {
+        for (rotation, rows) in [
+            (Arc::new(Srs) as Arc<dyn RotationSystem>, 2),
+            (Arc::new(Ars), 0),
+            (Arc::new(Nrs), 0),
+        ] {
+            let g = Game::with_config(GameConfig {
+                hidden_rows: 0,
+                rotation,
+                ..GameConfig::default()
+            });
+            assert_eq!(g.config().hidden_rows, rows);
+            assert!(g.blocks(g.current_piece()).iter().all(|&(_, y)| y >= -rows));
+        }
+    }
+
+    #[test]
+    fn vanishing_zone_keeps_blocks_above_the_field() {
+        let mut g = game_with(Tetromino::I, 1, 3, -4);
+        fill(&mut g, &["#####.###."; 20]);
+        g.board.set(5, 0, 1);
+        g.hard_drop();
+        // The I rests on top of the column, fully inside the vanishing zone.
+        for y in -4..0 {
+            assert_eq!(g.cell(5, y), Tetromino::I.id());
+        }
+        assert_eq!(g.full_board().len(), (BOARD_W * (BOARD_H + 20)) as usize);
+        assert_eq!(g.visible_board().len(), (BOARD_W * BOARD_H) as usize);
+    }
+
+    #[test]
+    fn kicks_stop_at_the_top_of_the_vanishing_zone() {
+        let mut g = Game::with_config(GameConfig {
+            hidden_rows: 0,
+            ..GameConfig::default()
+        });
+        assert_eq!(g.config().hidden_rows, 2);
+        fill(&mut g, &["#########."; 20]);
+        g.current = Piece {
+            kind: Tetromino::J,
+            rot: 0,
+            x: 6,
+            y: -2,
+        };
+        g.update_grounded();
+        let filled = |g: &Game| g.full_board().iter().filter(|&&c| c != 0).count();
+        let before = filled(&g);
+
+        // The upward kick would leave the board, so the rotation fails.
+        g.rotate_cw();
+        assert!(g.blocks(g.current_piece()).iter().all(|&(_, y)| y >= -2));
+        g.hard_drop();
+        assert_eq!(filled(&g), before + 4);
+    }
+
+    #[test]
+    fn top_out_reasons() {
+        // Lock out: the whole piece rests above the visible field.
+        let mut g = game_with(Tetromino::O, 0, 3, -3);
+        fill(&mut g, &["....##...."; 20]);
+        g.hard_drop();
+        assert_eq!(g.game_over_reason(), Some(GameOverReason::LockOut));
+        assert_eq!(g.hard_drop(), Step::GameOver);
+
+        // Straddling the top edge only counts with partial lock out enabled.
+        let mut g = game_with(Tetromino::I, 1, -2, -3);
+        fill(&mut g, &["#........."; 19]);
+        g.hard_drop();
+        assert!(!g.is_game_over());
+        let mut g = game_with(Tetromino::I, 1, -2, -3);
+        g.config.top_out.partial_lock_out = true;
+        fill(&mut g, &["#........."; 19]);
+        g.hard_drop();
+        assert_eq!(g.game_over_reason(), Some(GameOverReason::PartialLockOut));
+
+        // Block out: the next piece has nowhere to appear.
+        let mut g = game_with(Tetromino::O, 0, 0, BOARD_H - 2);
+        fill(&mut g, &["..########"; 20]);
+        for y in -2..0 {
+            for x in 2..BOARD_W {
+                g.board.set(x, y, 1);
+            }
+        }
+        g.hard_drop();
+        assert_eq!(g.game_over_reason(), Some(GameOverReason::BlockOut));
+    }
+
+    #[test]
+    fn spawn_drop_moves_piece_down_when_clear() {
+        let mut g = Game::new();
+        g.config.spawn_drop = false;
+        g.reset_with_seed(1);
+        assert_eq!(g.current_piece().y, -2);
+        g.config.spawn_drop = true;
+        g.reset_with_seed(1);
+        assert_eq!(g.current_piece().y, -1);
+    }
+
+    #[test]
+    fn initial_rotation_and_hold_apply_at_spawn() {
+        let mut g = Game::with_seed(3);
+        let upcoming: Vec<_> = g.next_queue().take(2).collect();
+
+        g.set_initial_hold(true);
+        g.set_initial_rotation(Some(Turn::Ccw));
+        g.hard_drop();
+        // The next piece went straight to hold and the one after spawned turned.
+        assert_eq!(g.held_piece(), Some(upcoming[0]));
+        assert_eq!(g.current_piece().kind, upcoming[1]);
+        assert_eq!(g.current_piece().rot, 3);
+        assert!(!g.can_hold());
+
+        g.hard_drop();
+        assert_eq!(g.current_piece().rot, 0);
+        assert_eq!(g.held_piece(), Some(upcoming[0]));
+    }
+
+    #[test]
+    fn hold_leaves_a_pending_initial_hold_alone() {
+        let mut g = Game::with_seed(3);
+        let first = g.current_piece().kind;
+        let upcoming: Vec<_> = g.next_queue().take(3).collect();
+
+        g.set_initial_hold(true);
+        g.hold();
+        assert_eq!(g.held_piece(), Some(first));
+        assert_eq!(g.current_piece().kind, upcoming[0]);
+
+        // The IHS still applies to the next spawn from the queue.
+        g.hard_drop();
+        assert_eq!(g.held_piece(), Some(upcoming[1]));
+        assert_eq!(g.current_piece().kind, first);
+        assert_eq!(g.next_queue().next(), Some(upcoming[2]));
+    }
+
+    #[test]
+    fn line_clear_delay_and_are_run_between_pieces() {
+        assert_eq!(Game::with_seed(1).phase(), Phase::Falling);
+        let mut g = game_with(Tetromino::I, 0, 4, 5);
+        g.config.line_clear_delay = ms(400);
+        g.config.are = ms(100);
+        fill(&mut g, &["#.........", "####....##"]);
+        let floor = BOARD_H - 1;
+
+        let Step::Locked(event) = g.hard_drop() else {
+            panic!("the I piece should lock");
+        };
+        assert_eq!(event.rows, [floor]);
+        assert_eq!(g.lines(), 1);
+        let rows = vec![floor];
+        let remaining = ms(400);
+        assert_eq!(g.phase(), Phase::LineClear { rows, remaining });
+        // The full row stays up for the animation and nothing is in play.
+        assert_ne!(g.cell(5, floor), 0);
+        assert_eq!(g.hard_drop(), Step::Waiting);
+        assert!(!g.can_hold());
+
+        let spawned = g.pieces_spawned();
+        assert_eq!(g.update(ms(450)), None);
+        assert_eq!(g.phase(), Phase::Are { remaining: ms(50) });
+        assert_eq!(g.cell(5, floor), 0);
+        assert_ne!(g.cell(0, floor), 0);
+        assert_eq!(g.pieces_spawned(), spawned);
+
+        g.update(ms(50));
+        assert_eq!(g.pieces_spawned(), spawned + 1);
+        assert_eq!(g.phase(), Phase::Falling);
+        g.hard_drop();
+        assert_eq!(g.phase(), Phase::Are { remaining: ms(100) });
+
+        let grounded = game_with(Tetromino::O, 0, 3, BOARD_H - 2);
+        assert_eq!(grounded.phase(), Phase::LockDelay);
+    }
+
+    fn game_with(kind: Tetromino, rot: u8, x: i32, y: i32) -> Game {
+        let mut g = Game::new();
+        g.current = Piece { kind, rot, x, y };
+        g.lowest_y = y;
+        g.lock_timer = None;
+        g.update_grounded();
+        g
+    }
+
+    fn fill(g: &mut Game, rows: &[&str]) {
+        // Rows are given top to bottom and aligned with the bottom of the board.
+        let top = BOARD_H - rows.len() as i32;
+        for (i, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    g.board.set(x as i32, top + i as i32, 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotations_round_trip_on_open_board() {
+        let mut g = game_with(Tetromino::T, 0, 4, 5);
+        g.rotate_cw();
+        assert_eq!(g.current_piece().rot, 1);
+        g.rotate_ccw();
+        assert_eq!(g.current_piece().rot, 0);
+        g.rotate_180();
+        assert_eq!(g.current_piece().rot, 2);
+        assert_eq!((g.current_piece().x, g.current_piece().y), (4, 5));
+    }
+
+    #[test]
+    fn i_piece_kicks_off_right_wall() {
+        // Vertical I hugging the right wall: the flat rotation must kick left.
+        let mut g = game_with(Tetromino::I, 1, BOARD_W - 3, 5);
+        g.rotate_cw();
+        let p = g.current_piece();
+        assert_eq!(p.rot, 2);
+        for (x, _) in g.blocks(p) {
+            assert!(x < BOARD_W);
+        }
+    }
+
+    #[test]
+    fn t_spin_triple_uses_vertical_kick() {
+        let mut g = game_with(Tetromino::T, 0, 0, BOARD_H - 5);
+        fill(
+            &mut g,
+            &[
+                "#.........",
+                "...#######",
+                ".#########",
+                "..########",
+                ".#########",
+            ],
+        );
+        // 0 -> R only fits with the last test, (-1, -2).
+        g.rotate_cw();
+        let p = g.current_piece();
+        assert_eq!(p.rot, 1);
+        assert_eq!((p.x, p.y), (-1, BOARD_H - 3));
     }
 }
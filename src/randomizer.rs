@@ -0,0 +1,356 @@
+use std::fmt;
+
+use rand::prelude::*;
+
+use crate::Tetromino;
+
+/// Chooses the order in which pieces are dealt.
+///
+/// Randomizers keep their own state (bag contents, history) but draw all
+/// randomness from the game's RNG, so a seeded game stays reproducible.
+pub trait Randomizer: fmt::Debug + Send + Sync {
+    /// Short identifier, e.g. `"7-bag"`.
+    fn name(&self) -> &'static str;
+
+    fn next(&mut self, rng: &mut dyn RngCore) -> Tetromino;
+
+    /// Whatever pieces the randomizer remembers (bag contents, history), so
+    /// a saved game can pick up where it left off.
+    fn state(&self) -> Vec<Tetromino> {
+        Vec::new()
+    }
+
+    /// Restores a `state` taken from a randomizer of the same name.
+    fn set_state(&mut self, _state: &[Tetromino]) {}
+
+    fn clone_box(&self) -> Box<dyn Randomizer>;
+}
+
+impl Clone for Box<dyn Randomizer> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// A fresh randomizer of the built-in kind called `name`, as returned by
+/// `Randomizer::name`.
+pub fn by_name(name: &str) -> Option<Box<dyn Randomizer>> {
+    Some(match name {
+        "random" => Box::new(PureRandom),
+        "7-bag" => Box::new(Bag::seven()),
+        "14-bag" => Box::new(Bag::fourteen()),
+        "tgm1" => Box::new(TgmHistory::tgm1()),
+        "tgm2" => Box::new(TgmHistory::tgm2()),
+        "nes" => Box::new(NesRandomizer::default()),
+        _ => return None,
+    })
+}
+
+// Saves a randomizer template as its name.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_name {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::Randomizer;
+
+    // serde's `with` hands over the field as it is declared.
+    #[allow(clippy::borrowed_box)]
+    pub fn serialize<S: Serializer>(
+        randomizer: &Box<dyn Randomizer>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(randomizer.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<dyn Randomizer>, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        super::by_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown randomizer {name:?}")))
+    }
+}
+
+// Saves a game's randomizer as its name and state.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_state {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Randomizer;
+    use crate::Tetromino;
+
+    #[derive(Serialize, Deserialize)]
+    struct Saved {
+        name: String,
+        state: Vec<Tetromino>,
+    }
+
+    // serde's `with` hands over the field as it is declared.
+    #[allow(clippy::borrowed_box)]
+    pub fn serialize<S: Serializer>(
+        randomizer: &Box<dyn Randomizer>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Saved {
+            name: randomizer.name().into(),
+            state: randomizer.state(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<dyn Randomizer>, D::Error> {
+        let saved = Saved::deserialize(deserializer)?;
+        let mut randomizer = super::by_name(&saved.name)
+            .ok_or_else(|| D::Error::custom(format!("unknown randomizer {:?}", saved.name)))?;
+        randomizer.set_state(&saved.state);
+        Ok(randomizer)
+    }
+}
+
+/// Every piece is equally likely on every draw, with no memory at all.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PureRandom;
+
+impl Randomizer for PureRandom {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn next(&mut self, rng: &mut dyn RngCore) -> Tetromino {
+        Tetromino::ALL[rng.random_range(0..7)]
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(*self)
+    }
+}
+
+/// Deals shuffled bags holding `copies` of each piece.
+#[derive(Debug, Clone)]
+pub struct Bag {
+    copies: usize,
+    bag: Vec<Tetromino>,
+}
+
+impl Bag {
+    /// The guideline 7-bag.
+    pub fn seven() -> Self {
+        Self::with_copies(1)
+    }
+
+    /// Two copies of every piece per bag, which allows short repeats.
+    pub fn fourteen() -> Self {
+        Self::with_copies(2)
+    }
+
+    fn with_copies(copies: usize) -> Self {
+        Self {
+            copies,
+            bag: Vec::with_capacity(7 * copies),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn name(&self) -> &'static str {
+        if self.copies == 1 { "7-bag" } else { "14-bag" }
+    }
+
+    fn next(&mut self, rng: &mut dyn RngCore) -> Tetromino {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend_from_slice(&Tetromino::ALL);
+            }
+            self.bag.shuffle(rng);
+        }
+        // Refilled above, so never empty here.
+        self.bag.pop().unwrap_or(Tetromino::I)
+    }
+
+    fn state(&self) -> Vec<Tetromino> {
+        self.bag.clone()
+    }
+
+    fn set_state(&mut self, state: &[Tetromino]) {
+        self.bag = state.to_vec();
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// TGM-style history randomizer: remembers the last 4 pieces and rerolls a
+/// few times when the draw is among them.
+#[derive(Debug, Clone)]
+pub struct TgmHistory {
+    rolls: u32,
+    history: [Tetromino; 4],
+    first: bool,
+}
+
+impl TgmHistory {
+    /// TGM1: 4 rolls, history starts as Z Z Z Z.
+    pub fn tgm1() -> Self {
+        Self {
+            rolls: 4,
+            history: [Tetromino::Z; 4],
+            first: true,
+        }
+    }
+
+    /// TGM2: 6 rolls, history starts as Z S S Z.
+    pub fn tgm2() -> Self {
+        Self {
+            rolls: 6,
+            history: [Tetromino::Z, Tetromino::S, Tetromino::S, Tetromino::Z],
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for TgmHistory {
+    fn name(&self) -> &'static str {
+        if self.rolls == 4 { "tgm1" } else { "tgm2" }
+    }
+
+    fn next(&mut self, rng: &mut dyn RngCore) -> Tetromino {
+        let piece = if self.first {
+            // The first piece is never S, Z or O.
+            self.first = false;
+            [Tetromino::I, Tetromino::T, Tetromino::J, Tetromino::L][rng.random_range(0..4)]
+        } else {
+            let mut piece = Tetromino::ALL[rng.random_range(0..7)];
+            for _ in 1..self.rolls {
+                if !self.history.contains(&piece) {
+                    break;
+                }
+                piece = Tetromino::ALL[rng.random_range(0..7)];
+            }
+            piece
+        };
+        self.history.rotate_right(1);
+        self.history[0] = piece;
+        piece
+    }
+
+    // Empty until the first draw, which follows its own rule.
+    fn state(&self) -> Vec<Tetromino> {
+        if self.first {
+            Vec::new()
+        } else {
+            self.history.to_vec()
+        }
+    }
+
+    fn set_state(&mut self, state: &[Tetromino]) {
+        if let Ok(history) = state.try_into() {
+            self.history = history;
+            self.first = false;
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+/// NES randomizer: rolls an 8-sided die and rerolls once when it lands on
+/// the blank side or repeats the previous piece.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct NesRandomizer {
+    last: Option<Tetromino>,
+}
+
+impl Randomizer for NesRandomizer {
+    fn name(&self) -> &'static str {
+        "nes"
+    }
+
+    fn next(&mut self, rng: &mut dyn RngCore) -> Tetromino {
+        let roll = rng.random_range(0..8);
+        let piece = match Tetromino::ALL.get(roll) {
+            Some(&p) if Some(p) != self.last => p,
+            _ => Tetromino::ALL[rng.random_range(0..7)],
+        };
+        self.last = Some(piece);
+        piece
+    }
+
+    fn state(&self) -> Vec<Tetromino> {
+        self.last.into_iter().collect()
+    }
+
+    fn set_state(&mut self, state: &[Tetromino]) {
+        self.last = state.first().copied();
+    }
+
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(r: &mut dyn Randomizer, n: usize) -> Vec<Tetromino> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..n).map(|_| r.next(&mut rng)).collect()
+    }
+
+    #[test]
+    fn seven_bag_deals_every_piece_once_per_bag() {
+        let pieces = draw(&mut Bag::seven(), 70);
+        for bag in pieces.chunks(7) {
+            for kind in Tetromino::ALL {
+                assert_eq!(bag.iter().filter(|&&p| p == kind).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn fourteen_bag_deals_every_piece_twice_per_bag() {
+        let pieces = draw(&mut Bag::fourteen(), 70);
+        for bag in pieces.chunks(14) {
+            for kind in Tetromino::ALL {
+                assert_eq!(bag.iter().filter(|&&p| p == kind).count(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn restored_state_deals_the_same_pieces() {
+        for name in ["random", "7-bag", "14-bag", "tgm1", "tgm2", "nes"] {
+            let mut original = by_name(name).unwrap();
+            let mut rng = StdRng::seed_from_u64(3);
+            for _ in 0..10 {
+                original.next(&mut rng);
+            }
+            let mut restored = by_name(name).unwrap();
+            restored.set_state(&original.state());
+            assert_eq!(draw(&mut *restored, 30), draw(&mut *original, 30), "{name}");
+        }
+    }
+
+    #[test]
+    fn tgm_never_opens_with_s_z_or_o() {
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let first = TgmHistory::tgm2().next(&mut rng);
+            assert!(!matches!(first, Tetromino::S | Tetromino::Z | Tetromino::O));
+        }
+    }
+
+    #[test]
+    fn boxed_randomizers_clone_their_state() {
+        let mut a: Box<dyn Randomizer> = Box::new(Bag::seven());
+        draw(a.as_mut(), 3);
+        let mut b = a.clone();
+        assert_eq!(draw(a.as_mut(), 11), draw(b.as_mut(), 11));
+    }
+}
@@ -0,0 +1,631 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::{
+    Command, Game, GameConfig, GravityCurve, InputBuffer, LockDelay, LockReset, MAX_BOARD_SIZE,
+    MAX_PREVIEW, MAX_START_LEVEL, ScoringRule, TimedCommand, TopOutRules, randomizer, rotation,
+};
+
+const MAGIC: &[u8; 4] = b"TRPL";
+
+/// Version written by [`Replay::to_bytes`]; bump on any layout change.
+pub const REPLAY_VERSION: u8 = 1;
+
+/// Everything needed to play a game again: seed, rules and the commands in
+/// the frames they ran, plus the result to check a playback against.
+///
+/// Record by feeding it what [`InputBuffer::step`] returns, then call
+/// [`finish`](Self::finish) when the game is over.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Replay {
+    pub seed: u64,
+    pub config: GameConfig,
+    /// Commands in the order they ran, so frames never decrease.
+    pub commands: Vec<TimedCommand>,
+    /// Frames the recording lasted.
+    pub frames: u64,
+    pub score: u32,
+    pub lines: u32,
+}
+
+/// Why a replay could not be read or did not play back as recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    /// The data does not start with the replay magic bytes.
+    NotAReplay,
+    UnsupportedVersion(u8),
+    /// The data ended in the middle of a field.
+    Truncated,
+    /// A field holds a value that no replay can contain.
+    Invalid(&'static str),
+    UnknownRotation(String),
+    UnknownRandomizer(String),
+    #[cfg(feature = "serde")]
+    Json(String),
+    /// Playback finished with a different result than the recording.
+    Mismatch {
+        score: (u32, u32),
+        lines: (u32, u32),
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::NotAReplay => write!(f, "not a replay"),
+            ReplayError::UnsupportedVersion(v) => write!(f, "unsupported replay version {v}"),
+            ReplayError::Truncated => write!(f, "replay data is truncated"),
+            ReplayError::Invalid(what) => write!(f, "invalid {what} in replay"),
+            ReplayError::UnknownRotation(name) => write!(f, "unknown rotation system {name:?}"),
+            ReplayError::UnknownRandomizer(name) => write!(f, "unknown randomizer {name:?}"),
+            #[cfg(feature = "serde")]
+            ReplayError::Json(e) => write!(f, "bad replay JSON: {e}"),
+            ReplayError::Mismatch { score, lines } => write!(
+                f,
+                "replay ended with score {} and {} lines, expected {} and {}",
+                score.1, lines.1, score.0, lines.0
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Replay {
+    /// An empty recording of a game started with `config` and `seed`.
+    pub fn new(config: GameConfig, seed: u64) -> Self {
+        Replay {
+            seed,
+            config,
+            commands: Vec::new(),
+            frames: 0,
+            score: 0,
+            lines: 0,
+        }
+    }
+
+    /// An empty recording for `game`, which must not have been played yet.
+    pub fn for_game(game: &Game) -> Self {
+        Self::new(game.config().clone(), game.seed())
+    }
+
+    /// Appends commands that ran, e.g. the `commands` of the
+    /// [`Frame`](crate::Frame) `InputBuffer::step` returns.
+    pub fn record(&mut self, commands: impl IntoIterator<Item = TimedCommand>) {
+        self.commands.extend(commands);
+    }
+
+    /// Ends the recording after `frames` frames with `game`'s result.
+    pub fn finish(&mut self, frames: u64, game: &Game) {
+        self.frames = frames;
+        self.score = game.score();
+        self.lines = game.lines();
+    }
+
+    /// Compact binary form: magic, version, then varint-packed fields and
+    /// commands as frame deltas. A command out of frame order is written on
+    /// the frame of the one before it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+        w.0.extend_from_slice(MAGIC);
+        w.0.push(REPLAY_VERSION);
+        w.0.extend_from_slice(&self.seed.to_le_bytes());
+        w.config(&self.config);
+        w.varint(self.frames);
+        w.varint(self.score as u64);
+        w.varint(self.lines as u64);
+        w.varint(self.commands.len() as u64);
+        let mut frame = 0;
+        for c in &self.commands {
+            w.varint(c.frame.saturating_sub(frame));
+            w.0.push(command_code(c.command));
+            frame = frame.max(c.frame);
+        }
+        w.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut r = Reader(bytes);
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        match r.byte()? {
+            REPLAY_VERSION => {}
+            v => return Err(ReplayError::UnsupportedVersion(v)),
+        }
+        let seed = u64::from_le_bytes(r.take(8)?.try_into().unwrap());
+        let config = r.config()?;
+        let frames = r.varint()?;
+        let score = r.u32()?;
+        let lines = r.u32()?;
+        let count = r.varint()?;
+        let mut commands = Vec::new();
+        let mut frame = 0u64;
+        for _ in 0..count {
+            frame = frame
+                .checked_add(r.varint()?)
+                .ok_or(ReplayError::Invalid("frame"))?;
+            let command = *Command::ALL
+                .get(r.byte()? as usize)
+                .ok_or(ReplayError::Invalid("command"))?;
+            commands.push(TimedCommand { frame, command });
+        }
+        if !r.0.is_empty() {
+            return Err(ReplayError::Invalid("trailing data"));
+        }
+        Ok(Replay {
+            seed,
+            config,
+            commands,
+            frames,
+            score,
+            lines,
+        })
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, ReplayError> {
+        serde_json::to_string(self).map_err(|e| ReplayError::Json(e.to_string()))
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Self, ReplayError> {
+        let replay: Self =
+            serde_json::from_str(json).map_err(|e| ReplayError::Json(e.to_string()))?;
+        if !replay.commands.is_sorted_by_key(|c| c.frame) {
+            return Err(ReplayError::Invalid("command order"));
+        }
+        Ok(replay)
+    }
+}
+
+/// Steps a fresh `Game` through a [`Replay`], one frame at a time.
+#[derive(Debug, Clone)]
+pub struct ReplayPlayer<'a> {
+    replay: &'a Replay,
+    game: Game,
+    input: InputBuffer,
+}
+
+impl<'a> ReplayPlayer<'a> {
+    pub fn new(replay: &'a Replay) -> Self {
+        // Nothing can drain events through `game()`, so they would only pile up.
+        let config = GameConfig {
+            record_events: false,
+            ..replay.config.clone()
+        };
+        let game = Game::with_config_and_seed(config, replay.seed);
+        let mut input = InputBuffer::new();
+        for c in &replay.commands {
+            input.push(c.frame, c.command);
+        }
+        ReplayPlayer {
+            replay,
+            game,
+            input,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The frame the next `step` plays.
+    pub fn frame(&self) -> u64 {
+        self.input.frame()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame() >= self.replay.frames
+    }
+
+    /// Plays one frame. Returns false once the recording has run out.
+    pub fn step(&mut self) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        self.input.step(&mut self.game);
+        true
+    }
+
+    /// Plays the rest of the replay and checks that the game ends with the
+    /// recorded score and lines.
+    pub fn run(mut self) -> Result<Game, ReplayError> {
+        while self.step() {}
+        let (score, lines) = (self.game.score(), self.game.lines());
+        if (score, lines) != (self.replay.score, self.replay.lines) {
+            return Err(ReplayError::Mismatch {
+                score: (self.replay.score, score),
+                lines: (self.replay.lines, lines),
+            });
+        }
+        Ok(self.game)
+    }
+}
+
+fn command_code(command: Command) -> u8 {
+    Command::ALL.iter().position(|&c| c == command).unwrap() as u8
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    // LEB128: seven bits per byte, high bit set on all but the last.
+    fn varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.0.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        self.0.push(v as u8);
+    }
+
+    fn signed(&mut self, v: i64) {
+        self.varint(((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.0.push(v as u8);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.varint(s.len() as u64);
+        self.0.extend_from_slice(s.as_bytes());
+    }
+
+    fn duration(&mut self, d: Duration) {
+        self.varint(d.as_nanos() as u64);
+    }
+
+    fn config(&mut self, c: &GameConfig) {
+        self.signed(c.width as i64);
+        self.signed(c.height as i64);
+        self.signed(c.hidden_rows as i64);
+        self.str(c.rotation.name());
+        self.str(c.randomizer.name());
+        self.varint(c.preview as u64);
+        self.duration(c.lock_delay.duration);
+        match c.lock_delay.reset {
+            LockReset::Move { limit } => {
+                self.0.push(0);
+                self.varint(limit as u64);
+            }
+            LockReset::Infinite => self.0.push(1),
+            LockReset::Step => self.0.push(2),
+        }
+        match &c.gravity {
+            GravityCurve::Guideline => self.0.push(0),
+            GravityCurve::Nes => self.0.push(1),
+            GravityCurve::Tgm => self.0.push(2),
+            GravityCurve::Custom(steps) => {
+                self.0.push(3);
+                self.varint(steps.len() as u64);
+                for &(level, g) in steps {
+                    self.varint(level as u64);
+                    self.0.extend_from_slice(&g.to_le_bytes());
+                }
+            }
+        }
+        self.varint(c.start_level as u64);
+        self.varint(c.lines_per_level as u64);
+        self.0.push(match c.scoring {
+            ScoringRule::Nes => 0,
+            ScoringRule::Guideline => 1,
+        });
+        self.bool(c.top_out.lock_out);
+        self.bool(c.top_out.partial_lock_out);
+        self.duration(c.are);
+        self.duration(c.line_clear_delay);
+        self.bool(c.spawn_drop);
+        self.bool(c.record_events);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ReplayError> {
+        if self.0.len() < n {
+            return Err(ReplayError::Truncated);
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(ReplayError::Invalid("varint"))
+    }
+
+    fn u32(&mut self) -> Result<u32, ReplayError> {
+        u32::try_from(self.varint()?).map_err(|_| ReplayError::Invalid("number"))
+    }
+
+    fn i32(&mut self) -> Result<i32, ReplayError> {
+        let v = self.varint()?;
+        let v = (v >> 1) as i64 ^ -((v & 1) as i64);
+        i32::try_from(v).map_err(|_| ReplayError::Invalid("number"))
+    }
+
+    fn bool(&mut self) -> Result<bool, ReplayError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ReplayError::Invalid("flag")),
+        }
+    }
+
+    fn str(&mut self) -> Result<&'a str, ReplayError> {
+        let len = self.varint()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|_| ReplayError::Invalid("name"))
+    }
+
+    fn duration(&mut self) -> Result<Duration, ReplayError> {
+        Ok(Duration::from_nanos(self.varint()?))
+    }
+
+    fn config(&mut self) -> Result<GameConfig, ReplayError> {
+        let width = self.i32()?;
+        let height = self.i32()?;
+        if !(4..=MAX_BOARD_SIZE).contains(&width) || !(4..=MAX_BOARD_SIZE).contains(&height) {
+            return Err(ReplayError::Invalid("board size"));
+        }
+        let hidden_rows = self.i32()?;
+        if !(0..=MAX_BOARD_SIZE).contains(&hidden_rows) {
+            return Err(ReplayError::Invalid("hidden rows"));
+        }
+        let name = self.str()?;
+        let rotation =
+            rotation::by_name(name).ok_or_else(|| ReplayError::UnknownRotation(name.into()))?;
+        let name = self.str()?;
+        let randomizer =
+            randomizer::by_name(name).ok_or_else(|| ReplayError::UnknownRandomizer(name.into()))?;
+        let preview = self.varint()?;
+        if !(1..=MAX_PREVIEW as u64).contains(&preview) {
+            return Err(ReplayError::Invalid("preview"));
+        }
+        let duration = self.duration()?;
+        let reset = match self.byte()? {
+            0 => LockReset::Move { limit: self.u32()? },
+            1 => LockReset::Infinite,
+            2 => LockReset::Step,
+            _ => return Err(ReplayError::Invalid("lock reset")),
+        };
+        let gravity = match self.byte()? {
+            0 => GravityCurve::Guideline,
+            1 => GravityCurve::Nes,
+            2 => GravityCurve::Tgm,
+            3 => {
+                let count = self.varint()?;
+                let mut steps = Vec::new();
+                for _ in 0..count {
+                    let level = self.u32()?;
+                    let g = f64::from_le_bytes(self.take(8)?.try_into().unwrap());
+                    steps.push((level, g));
+                }
+                GravityCurve::Custom(steps)
+            }
+            _ => return Err(ReplayError::Invalid("gravity")),
+        };
+        let start_level = self.u32()?;
+        if start_level > MAX_START_LEVEL {
+            return Err(ReplayError::Invalid("start level"));
+        }
+        let lines_per_level = self.u32()?;
+        let scoring = match self.byte()? {
+            0 => ScoringRule::Nes,
+            1 => ScoringRule::Guideline,
+            _ => return Err(ReplayError::Invalid("scoring")),
+        };
+        Ok(GameConfig {
+            width,
+            height,
+            hidden_rows,
+            rotation,
+            randomizer,
+            preview: preview as usize,
+            lock_delay: LockDelay { duration, reset },
+            gravity,
+            start_level,
+            lines_per_level,
+            scoring,
+            top_out: TopOutRules {
+                lock_out: self.bool()?,
+                partial_lock_out: self.bool()?,
+            },
+            are: self.duration()?,
+            line_clear_delay: self.duration()?,
+            spawn_drop: self.bool()?,
+            record_events: self.bool()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::frame_duration;
+    use crate::{Ars, InputController, Key, TgmHistory};
+    use std::sync::Arc;
+
+    // Plays a short scripted game through an input buffer while recording it.
+    fn record(config: GameConfig, seed: u64) -> Replay {
+        let mut game = Game::with_config_and_seed(config, seed);
+        let mut replay = Replay::for_game(&game);
+        let mut input = InputBuffer::new();
+        let script = [
+            Command::Left,
+            Command::RotateCw,
+            Command::HardDrop,
+            Command::Hold,
+            Command::Right,
+            Command::Right,
+            Command::SoftDrop,
+            Command::HardDrop,
+        ];
+        for (i, &command) in script.iter().cycle().take(60).enumerate() {
+            input.push(i as u64 * 7, command);
+        }
+        while input.frame() < 500 {
+            replay.record(input.step(&mut game).commands);
+        }
+        replay.finish(input.frame(), &game);
+        replay
+    }
+
+    #[test]
+    fn binary_round_trip_plays_back() {
+        let config = GameConfig {
+            rotation: Arc::new(Ars),
+            randomizer: Box::new(TgmHistory::tgm2()),
+            gravity: GravityCurve::Custom(vec![(1, 0.05), (3, 1.5)]),
+            lock_delay: LockDelay {
+                duration: Duration::from_millis(300),
+                reset: LockReset::Step,
+            },
+            hidden_rows: 2,
+            ..GameConfig::default()
+        };
+        let replay = record(config, 42);
+        assert!(replay.score > 0);
+
+        let bytes = replay.to_bytes();
+        let decoded = Replay::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.config.rotation.name(), "ars");
+        assert_eq!(decoded.config.gravity, replay.config.gravity);
+
+        let game = ReplayPlayer::new(&decoded).run().unwrap();
+        assert_eq!(game.score(), replay.score);
+    }
+
+    #[test]
+    fn controller_driven_games_play_back() {
+        let mut game = Game::with_seed(21);
+        let mut replay = Replay::for_game(&game);
+        let mut input = InputBuffer::new();
+        let mut controls = InputController::default();
+        for frame in 0..1200u64 {
+            // Hold a direction for a while, soft drop, then hard drop.
+            match frame % 60 {
+                0 if frame % 120 == 0 => controls.press(&mut game, &mut input, Key::Left),
+                0 => controls.press(&mut game, &mut input, Key::Right),
+                25 => {
+                    controls.release(Key::Left);
+                    controls.release(Key::Right);
+                    controls.press(&mut game, &mut input, Key::SoftDrop);
+                }
+                40 => controls.release(Key::SoftDrop),
+                50 => input.push_now(Command::HardDrop),
+                _ => {}
+            }
+            controls.update(&mut game, &mut input, frame_duration());
+            replay.record(input.step(&mut game).commands);
+        }
+        replay.finish(input.frame(), &game);
+        assert!(replay.commands.len() > 100);
+
+        let played = ReplayPlayer::new(&replay).run().unwrap();
+        assert_eq!(played.full_board(), game.full_board());
+    }
+
+    #[test]
+    fn playback_does_not_queue_events() {
+        let replay = record(
+            GameConfig {
+                record_events: true,
+                ..GameConfig::default()
+            },
+            7,
+        );
+        let mut played = ReplayPlayer::new(&replay).run().unwrap();
+        assert_eq!(played.drain_events().count(), 0);
+    }
+
+    #[test]
+    fn playback_detects_a_different_result() {
+        let mut replay = record(GameConfig::default(), 7);
+        replay.score += 1;
+        assert!(matches!(
+            ReplayPlayer::new(&replay).run(),
+            Err(ReplayError::Mismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        let bytes = record(GameConfig::default(), 7).to_bytes();
+        assert_eq!(
+            Replay::from_bytes(b"nope").unwrap_err(),
+            ReplayError::NotAReplay
+        );
+        assert_eq!(
+            Replay::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            ReplayError::Truncated
+        );
+        let mut newer = bytes.clone();
+        newer[4] = REPLAY_VERSION + 1;
+        assert_eq!(
+            Replay::from_bytes(&newer).unwrap_err(),
+            ReplayError::UnsupportedVersion(REPLAY_VERSION + 1)
+        );
+
+        let mut huge = record(GameConfig::default(), 7);
+        huge.config.width = i32::MAX;
+        assert_eq!(
+            Replay::from_bytes(&huge.to_bytes()).unwrap_err(),
+            ReplayError::Invalid("board size")
+        );
+        huge.config.width = 10;
+        huge.config.hidden_rows = -1;
+        assert_eq!(
+            Replay::from_bytes(&huge.to_bytes()).unwrap_err(),
+            ReplayError::Invalid("hidden rows")
+        );
+        huge.config.hidden_rows = 2;
+        huge.config.preview = 1 << 40;
+        assert_eq!(
+            Replay::from_bytes(&huge.to_bytes()).unwrap_err(),
+            ReplayError::Invalid("preview")
+        );
+        huge.config.preview = 5;
+        huge.config.start_level = u32::MAX;
+        assert_eq!(
+            Replay::from_bytes(&huge.to_bytes()).unwrap_err(),
+            ReplayError::Invalid("start level")
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let replay = record(GameConfig::default(), 9);
+        let json = replay.to_json().unwrap();
+        assert!(json.contains("\"7-bag\""));
+        let decoded = Replay::from_json(&json).unwrap();
+        assert_eq!(decoded.to_bytes(), replay.to_bytes());
+        ReplayPlayer::new(&decoded).run().unwrap();
+
+        let mut shuffled = replay.clone();
+        shuffled.commands.swap(0, 1);
+        shuffled.commands[0].frame += 1;
+        assert_eq!(
+            Replay::from_json(&shuffled.to_json().unwrap()).unwrap_err(),
+            ReplayError::Invalid("command order")
+        );
+        // Still encodes, with the late command moved up to its predecessor.
+        assert!(Replay::from_bytes(&shuffled.to_bytes()).is_ok());
+    }
+}
@@ -0,0 +1,519 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Piece, Tetromino};
+
+/// Block layout for every piece and rotation state, indexed by
+/// `[kind][rot]`. Offsets are `(dx, dy)` from the piece origin.
+pub type ShapeTable = [[[(i8, i8); 4]; 4]; 7];
+
+/// Where each piece appears, indexed like `ShapeTable`: `(dx, y)` where
+/// `dx` shifts the piece box from the board's center (a 4-wide box, rounded
+/// left) and `y` is the row of the box origin.
+pub type SpawnTable = [(i8, i8); 7];
+
+/// The built-in rotation system called `name`, as returned by
+/// `RotationSystem::name`.
+pub fn by_name(name: &str) -> Option<Arc<dyn RotationSystem>> {
+    Some(match name {
+        "srs" => Arc::new(Srs),
+        "ars" => Arc::new(Ars),
+        "nrs" => Arc::new(Nrs),
+        _ => return None,
+    })
+}
+
+// Saves a rotation system as its name.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_name {
+    use std::sync::Arc;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::RotationSystem;
+
+    pub fn serialize<S: Serializer>(
+        rotation: &Arc<dyn RotationSystem>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(rotation.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<dyn RotationSystem>, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        super::by_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown rotation system {name:?}")))
+    }
+}
+
+/// Decides how pieces look, where they spawn and how they kick.
+///
+/// `rot` is always a rotation state in `0..4`, counted in clockwise quarter
+/// turns from the spawn orientation. Coordinates use the board's convention:
+/// x grows to the right and y grows downwards.
+pub trait RotationSystem: fmt::Debug + Send + Sync {
+    /// Short identifier, e.g. `"srs"`.
+    fn name(&self) -> &'static str;
+
+    /// Block offsets of `kind` in rotation state `rot`.
+    fn shape(&self, kind: Tetromino, rot: u8) -> [(i8, i8); 4];
+
+    /// Where a fresh piece of `kind` appears on a board `width` cells wide.
+    fn spawn(&self, kind: Tetromino, width: i32) -> Piece;
+
+    /// Turns `piece` by `turns` clockwise quarter turns, trying whatever kicks
+    /// the system allows. `occupied` reports walls, floor and filled cells.
+    fn rotate(&self, piece: Piece, turns: u8, occupied: &dyn Fn(i32, i32) -> bool)
+    -> Option<Piece>;
+
+    fn blocks(&self, piece: Piece) -> [(i32, i32); 4] {
+        let shape = self.shape(piece.kind, piece.rot % 4);
+        let mut out = [(0, 0); 4];
+        for (i, (dx, dy)) in shape.iter().enumerate() {
+            out[i] = (piece.x + (*dx as i32), piece.y + (*dy as i32));
+        }
+        out
+    }
+
+    fn fits(&self, piece: Piece, occupied: &dyn Fn(i32, i32) -> bool) -> bool {
+        self.blocks(piece).iter().all(|&(x, y)| !occupied(x, y))
+    }
+}
+
+fn shape_index(kind: Tetromino) -> usize {
+    (kind.id() as usize) - 1
+}
+
+fn spawn_from(table: &SpawnTable, kind: Tetromino, width: i32) -> Piece {
+    let (dx, y) = table[shape_index(kind)];
+    Piece {
+        kind,
+        rot: 0,
+        x: (width - 4) / 2 + dx as i32,
+        y: y as i32,
+    }
+}
+
+fn turned(piece: Piece, turns: u8) -> Piece {
+    Piece {
+        rot: (piece.rot + turns) % 4,
+        ..piece
+    }
+}
+
+// 4 rotations, each with 4 blocks, in a 4x4 local grid.
+// (dx, dy) are offsets from the piece's (x, y) origin.
+const SRS_SHAPES: ShapeTable = [
+    // I
+    [
+        [(0, 1), (1, 1), (2, 1), (3, 1)],
+        [(2, 0), (2, 1), (2, 2), (2, 3)],
+        [(0, 2), (1, 2), (2, 2), (3, 2)],
+        [(1, 0), (1, 1), (1, 2), (1, 3)],
+    ],
+    // O
+    [
+        [(1, 0), (2, 0), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (2, 1)],
+    ],
+    // T
+    [
+        [(1, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (1, 1), (2, 1), (1, 2)],
+        [(0, 1), (1, 1), (2, 1), (1, 2)],
+        [(1, 0), (0, 1), (1, 1), (1, 2)],
+    ],
+    // S
+    [
+        [(1, 0), (2, 0), (0, 1), (1, 1)],
+        [(1, 0), (1, 1), (2, 1), (2, 2)],
+        [(1, 1), (2, 1), (0, 2), (1, 2)],
+        [(0, 0), (0, 1), (1, 1), (1, 2)],
+    ],
+    // Z
+    [
+        [(0, 0), (1, 0), (1, 1), (2, 1)],
+        [(2, 0), (1, 1), (2, 1), (1, 2)],
+        [(0, 1), (1, 1), (1, 2), (2, 2)],
+        [(1, 0), (0, 1), (1, 1), (0, 2)],
+    ],
+    // J
+    [
+        [(0, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (1, 2)],
+        [(0, 1), (1, 1), (2, 1), (2, 2)],
+        [(1, 0), (1, 1), (0, 2), (1, 2)],
+    ],
+    // L
+    [
+        [(2, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (1, 1), (1, 2), (2, 2)],
+        [(0, 1), (1, 1), (2, 1), (0, 2)],
+        [(0, 0), (1, 0), (1, 1), (1, 2)],
+    ],
+];
+
+// SRS wall kick tests, as published in the guideline (x right, y up).
+// Each row is tried in order; the first offset that fits wins.
+const JLSTZ_KICKS: [[(i8, i8); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 0 -> R
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 0
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 2
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 2 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 2 -> L
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 2
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 0
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 0 -> L
+];
+
+const I_KICKS: [[(i8, i8); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // 0 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // R -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // R -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // 2 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // 2 -> L
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // L -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // L -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // 0 -> L
+];
+
+// The guideline defines no 180 kicks; these are the common SRS+ ones.
+const HALF_TURN_KICKS: [[(i8, i8); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)], // 0 -> 2
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],   // R -> L
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)], // 2 -> 0
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)], // L -> R
+];
+
+fn kicks_for(kind: Tetromino, from: u8, to: u8) -> &'static [(i8, i8)] {
+    let from = from % 4;
+    let to = to % 4;
+    if (from + 2) % 4 == to {
+        return &HALF_TURN_KICKS[from as usize];
+    }
+    // Transitions are laid out as 0->R, R->0, R->2, 2->R, 2->L, L->2, L->0, 0->L.
+    let row = match (from, to) {
+        (0, 1) => 0,
+        (1, 0) => 1,
+        (1, 2) => 2,
+        (2, 1) => 3,
+        (2, 3) => 4,
+        (3, 2) => 5,
+        (3, 0) => 6,
+        _ => 7,
+    };
+    match kind {
+        Tetromino::O => &JLSTZ_KICKS[0][..1],
+        Tetromino::I => &I_KICKS[row],
+        _ => &JLSTZ_KICKS[row],
+    }
+}
+
+// Guideline: every piece starts in the two rows just above the visible field.
+const SRS_SPAWN: SpawnTable = [(0, -2); 7];
+
+/// Super Rotation System, as used by the guideline games.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn name(&self) -> &'static str {
+        "srs"
+    }
+
+    fn shape(&self, kind: Tetromino, rot: u8) -> [(i8, i8); 4] {
+        SRS_SHAPES[shape_index(kind)][(rot % 4) as usize]
+    }
+
+    fn spawn(&self, kind: Tetromino, width: i32) -> Piece {
+        spawn_from(&SRS_SPAWN, kind, width)
+    }
+
+    fn rotate(
+        &self,
+        piece: Piece,
+        turns: u8,
+        occupied: &dyn Fn(i32, i32) -> bool,
+    ) -> Option<Piece> {
+        let rotated = turned(piece, turns);
+        for &(dx, dy) in kicks_for(piece.kind, piece.rot, rotated.rot) {
+            let mut candidate = rotated;
+            candidate.x += dx as i32;
+            // Kick tables are written with y pointing up; the board's y points down.
+            candidate.y -= dy as i32;
+            if self.fits(candidate, occupied) {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+}
+
+// Sega/TGM layouts: pieces rest on the bottom of their 3x3 box and the
+// 2-state pieces repeat themselves. Listed in reading order, which the
+// center column rule relies on.
+const ARS_SHAPES: ShapeTable = [
+    // I
+    [
+        [(0, 1), (1, 1), (2, 1), (3, 1)],
+        [(2, 0), (2, 1), (2, 2), (2, 3)],
+        [(0, 1), (1, 1), (2, 1), (3, 1)],
+        [(2, 0), (2, 1), (2, 2), (2, 3)],
+    ],
+    // O
+    [
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+    ],
+    // T
+    [
+        [(0, 1), (1, 1), (2, 1), (1, 2)],
+        [(1, 0), (0, 1), (1, 1), (1, 2)],
+        [(1, 1), (0, 2), (1, 2), (2, 2)],
+        [(1, 0), (1, 1), (2, 1), (1, 2)],
+    ],
+    // S
+    [
+        [(1, 1), (2, 1), (0, 2), (1, 2)],
+        [(0, 0), (0, 1), (1, 1), (1, 2)],
+        [(1, 1), (2, 1), (0, 2), (1, 2)],
+        [(0, 0), (0, 1), (1, 1), (1, 2)],
+    ],
+    // Z
+    [
+        [(0, 1), (1, 1), (1, 2), (2, 2)],
+        [(2, 0), (1, 1), (2, 1), (1, 2)],
+        [(0, 1), (1, 1), (1, 2), (2, 2)],
+        [(2, 0), (1, 1), (2, 1), (1, 2)],
+    ],
+    // J
+    [
+        [(0, 1), (1, 1), (2, 1), (2, 2)],
+        [(1, 0), (1, 1), (0, 2), (1, 2)],
+        [(0, 1), (0, 2), (1, 2), (2, 2)],
+        [(1, 0), (2, 0), (1, 1), (1, 2)],
+    ],
+    // L
+    [
+        [(0, 1), (1, 1), (2, 1), (0, 2)],
+        [(0, 0), (1, 0), (1, 1), (1, 2)],
+        [(2, 1), (0, 2), (1, 2), (2, 2)],
+        [(1, 0), (1, 1), (1, 2), (2, 2)],
+    ],
+];
+
+// TGM: pieces start in the top two visible rows.
+const ARS_SPAWN: SpawnTable = [(0, -1); 7];
+
+/// Arika Rotation System from the TGM series: one step right, then one step
+/// left, with the center column rule for J, L and T. The I piece never kicks.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Ars;
+
+impl Ars {
+    // A J, L or T whose first blocked cell (in reading order) sits in the
+    // middle column of its box is not allowed to kick.
+    fn center_column_blocked(&self, rotated: Piece, occupied: &dyn Fn(i32, i32) -> bool) -> bool {
+        for (dx, dy) in self.shape(rotated.kind, rotated.rot) {
+            if occupied(rotated.x + dx as i32, rotated.y + dy as i32) {
+                return dx == 1;
+            }
+        }
+        false
+    }
+}
+
+impl RotationSystem for Ars {
+    fn name(&self) -> &'static str {
+        "ars"
+    }
+
+    fn shape(&self, kind: Tetromino, rot: u8) -> [(i8, i8); 4] {
+        ARS_SHAPES[shape_index(kind)][(rot % 4) as usize]
+    }
+
+    fn spawn(&self, kind: Tetromino, width: i32) -> Piece {
+        spawn_from(&ARS_SPAWN, kind, width)
+    }
+
+    fn rotate(
+        &self,
+        piece: Piece,
+        turns: u8,
+        occupied: &dyn Fn(i32, i32) -> bool,
+    ) -> Option<Piece> {
+        let rotated = turned(piece, turns);
+        if self.fits(rotated, occupied) {
+            return Some(rotated);
+        }
+        match rotated.kind {
+            Tetromino::I => return None,
+            Tetromino::J | Tetromino::L | Tetromino::T
+                if self.center_column_blocked(rotated, occupied) =>
+            {
+                return None;
+            }
+            _ => {}
+        }
+        for dx in [1, -1] {
+            let mut candidate = rotated;
+            candidate.x += dx;
+            if self.fits(candidate, occupied) {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+}
+
+// NES layouts: every piece turns around a fixed center cell.
+const NRS_SHAPES: ShapeTable = [
+    // I
+    [
+        [(0, 2), (1, 2), (2, 2), (3, 2)],
+        [(2, 0), (2, 1), (2, 2), (2, 3)],
+        [(0, 2), (1, 2), (2, 2), (3, 2)],
+        [(2, 0), (2, 1), (2, 2), (2, 3)],
+    ],
+    // O
+    [
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+        [(1, 1), (2, 1), (1, 2), (2, 2)],
+    ],
+    // T
+    [
+        [(0, 1), (1, 1), (2, 1), (1, 2)],
+        [(1, 0), (0, 1), (1, 1), (1, 2)],
+        [(1, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (1, 1), (2, 1), (1, 2)],
+    ],
+    // S
+    [
+        [(1, 1), (2, 1), (0, 2), (1, 2)],
+        [(1, 0), (1, 1), (2, 1), (2, 2)],
+        [(1, 1), (2, 1), (0, 2), (1, 2)],
+        [(1, 0), (1, 1), (2, 1), (2, 2)],
+    ],
+    // Z
+    [
+        [(0, 1), (1, 1), (1, 2), (2, 2)],
+        [(2, 0), (1, 1), (2, 1), (1, 2)],
+        [(0, 1), (1, 1), (1, 2), (2, 2)],
+        [(2, 0), (1, 1), (2, 1), (1, 2)],
+    ],
+    // J
+    [
+        [(0, 1), (1, 1), (2, 1), (2, 2)],
+        [(1, 0), (1, 1), (0, 2), (1, 2)],
+        [(0, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (2, 0), (1, 1), (1, 2)],
+    ],
+    // L
+    [
+        [(0, 1), (1, 1), (2, 1), (0, 2)],
+        [(0, 0), (1, 0), (1, 1), (1, 2)],
+        [(2, 0), (0, 1), (1, 1), (2, 1)],
+        [(1, 0), (1, 1), (1, 2), (2, 2)],
+    ],
+];
+
+// NES: every piece's center cell starts on the top visible row, so the
+// 3-wide pieces sit one column right of I and O.
+const NRS_SPAWN: SpawnTable = [
+    (0, -2), // I
+    (0, -1), // O
+    (1, -1), // T
+    (1, -1), // S
+    (1, -1), // Z
+    (1, -1), // J
+    (1, -1), // L
+];
+
+/// Nintendo Rotation System from NES Tetris: rotations either fit in place
+/// or fail, there are no kicks at all.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Nrs;
+
+impl RotationSystem for Nrs {
+    fn name(&self) -> &'static str {
+        "nrs"
+    }
+
+    fn shape(&self, kind: Tetromino, rot: u8) -> [(i8, i8); 4] {
+        NRS_SHAPES[shape_index(kind)][(rot % 4) as usize]
+    }
+
+    fn spawn(&self, kind: Tetromino, width: i32) -> Piece {
+        spawn_from(&NRS_SPAWN, kind, width)
+    }
+
+    fn rotate(
+        &self,
+        piece: Piece,
+        turns: u8,
+        occupied: &dyn Fn(i32, i32) -> bool,
+    ) -> Option<Piece> {
+        let rotated = turned(piece, turns);
+        self.fits(rotated, occupied).then_some(rotated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walls(width: i32) -> impl Fn(i32, i32) -> bool {
+        move |x, _| !(0..width).contains(&x)
+    }
+
+    #[test]
+    fn nrs_never_kicks() {
+        // Vertical I flush with the left wall cannot lie flat without a kick.
+        let piece = Piece {
+            kind: Tetromino::I,
+            rot: 1,
+            x: -2,
+            y: 5,
+        };
+        assert!(Nrs.rotate(piece, 1, &walls(10)).is_none());
+        assert!(Srs.rotate(piece, 1, &walls(10)).is_some());
+    }
+
+    #[test]
+    fn ars_kicks_right_off_the_left_wall() {
+        let piece = Piece {
+            kind: Tetromino::T,
+            rot: 1,
+            x: -1,
+            y: 5,
+        };
+        let rotated = Ars.rotate(piece, 1, &walls(10)).unwrap();
+        assert_eq!((rotated.rot, rotated.x, rotated.y), (2, 0, 5));
+    }
+
+    #[test]
+    fn ars_center_column_blocks_kick() {
+        let piece = Piece {
+            kind: Tetromino::L,
+            rot: 0,
+            x: 4,
+            y: 5,
+        };
+        // A block above the middle of the box stops the rotation outright.
+        let occupied = |x, y| (x, y) == (5, 5);
+        assert!(Ars.rotate(piece, 1, &occupied).is_none());
+        // The same block one column to the left only pushes the piece aside.
+        let occupied = |x, y| (x, y) == (4, 5);
+        let rotated = Ars.rotate(piece, 1, &occupied).unwrap();
+        assert_eq!((rotated.rot, rotated.x), (1, 5));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::TSpin;
+
+/// Which scoring table a game uses.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScoringRule {
+    /// 40 / 100 / 300 / 1200 times level, no bonuses and no drop points.
+    Nes,
+    /// Modern guideline scoring: T-spins, back-to-back, combos, drop points
+    /// and perfect clears.
+    #[default]
+    Guideline,
+}
+
+/// Tetrises and T-spins that clear lines keep a back-to-back chain alive.
+pub fn is_difficult(lines: u32, spin: TSpin) -> bool {
+    lines == 4 || (lines > 0 && spin != TSpin::None)
+}
+
+impl ScoringRule {
+    /// Points for locking a piece at `level`. `back_to_back` is whether this
+    /// clear continues a back-to-back chain, and `combo` is the guideline
+    /// combo counter after this lock (`Some(0)` for the first clear).
+    pub fn lock_points(
+        self,
+        lines: u32,
+        spin: TSpin,
+        perfect: bool,
+        level: u32,
+        back_to_back: bool,
+        combo: Option<u32>,
+    ) -> u32 {
+        match self {
+            ScoringRule::Nes => {
+                let base: u32 = match lines {
+                    1 => 40,
+                    2 => 100,
+                    3 => 300,
+                    4 => 1200,
+                    _ => 0,
+                };
+                base.saturating_mul(level)
+            }
+            ScoringRule::Guideline => {
+                let mut base: u32 = match (spin, lines) {
+                    (TSpin::None, 1) => 100,
+                    (TSpin::None, 2) => 300,
+                    (TSpin::None, 3) => 500,
+                    (TSpin::None, 4) => 800,
+                    (TSpin::Mini, 0) => 100,
+                    (TSpin::Mini, 1) => 200,
+                    (TSpin::Mini, _) => 400,
+                    (TSpin::Full, 0) => 400,
+                    (TSpin::Full, 1) => 800,
+                    (TSpin::Full, 2) => 1200,
+                    (TSpin::Full, _) => 1600,
+                    _ => 0,
+                };
+                if back_to_back && is_difficult(lines, spin) {
+                    base = base * 3 / 2;
+                }
+                if perfect {
+                    base += match lines {
+                        1 => 800,
+                        2 => 1200,
+                        3 => 1800,
+                        _ if back_to_back => 3200,
+                        _ => 2000,
+                    };
+                }
+                let combo = if lines > 0 { combo.unwrap_or(0) } else { 0 };
+                base.saturating_add(50u32.saturating_mul(combo))
+                    .saturating_mul(level)
+            }
+        }
+    }
+
+    pub fn soft_drop_points(self, cells: u32) -> u32 {
+        match self {
+            ScoringRule::Nes => 0,
+            ScoringRule::Guideline => cells,
+        }
+    }
+
+    pub fn hard_drop_points(self, cells: u32) -> u32 {
+        match self {
+            ScoringRule::Nes => 0,
+            ScoringRule::Guideline => 2 * cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const G: ScoringRule = ScoringRule::Guideline;
+
+    #[test]
+    fn nes_table_is_unchanged() {
+        let nes = ScoringRule::Nes;
+        assert_eq!(
+            nes.lock_points(4, TSpin::None, false, 2, true, Some(3)),
+            2400
+        );
+        assert_eq!(nes.lock_points(1, TSpin::Full, false, 1, false, None), 40);
+        assert_eq!(nes.hard_drop_points(10), 0);
+    }
+
+    #[test]
+    fn guideline_spins_and_back_to_back() {
+        assert_eq!(G.lock_points(4, TSpin::None, false, 1, false, Some(0)), 800);
+        assert_eq!(G.lock_points(4, TSpin::None, false, 1, true, Some(0)), 1200);
+        assert_eq!(G.lock_points(2, TSpin::Full, false, 3, true, Some(0)), 5400);
+        assert_eq!(G.lock_points(0, TSpin::Mini, false, 1, true, None), 100);
+        // Ordinary clears never get the bonus.
+        assert_eq!(G.lock_points(2, TSpin::None, false, 1, true, Some(0)), 300);
+    }
+
+    #[test]
+    fn guideline_combos_and_perfect_clears() {
+        assert_eq!(G.lock_points(1, TSpin::None, false, 2, false, Some(3)), 500);
+        assert_eq!(G.lock_points(4, TSpin::None, true, 1, false, Some(0)), 2800);
+        assert_eq!(G.lock_points(4, TSpin::None, true, 1, true, Some(0)), 4400);
+        assert_eq!(G.soft_drop_points(3) + G.hard_drop_points(5), 13);
+    }
+
+    #[test]
+    fn huge_levels_saturate() {
+        let nes = ScoringRule::Nes;
+        assert_eq!(
+            nes.lock_points(4, TSpin::None, false, u32::MAX, false, None),
+            u32::MAX
+        );
+        assert_eq!(
+            G.lock_points(1, TSpin::None, false, u32::MAX, false, Some(u32::MAX)),
+            u32::MAX
+        );
+    }
+}
@@ -0,0 +1,47 @@
+/// How a T piece was spun into place, by the 3-corner rule.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TSpin {
+    #[default]
+    None,
+    /// Three corners filled, but only one of the two in front of the nub.
+    Mini,
+    Full,
+}
+
+/// Classifies a locked T piece. `kick` is the offset the last rotation was
+/// moved by; a 1-by-2 kick (the SRS "TST" test) always upgrades to a full
+/// T-spin. Walls and floor count as filled corners.
+pub(crate) fn detect(
+    blocks: [(i32, i32); 4],
+    kick: (i32, i32),
+    occupied: &dyn Fn(i32, i32) -> bool,
+) -> TSpin {
+    let adjacent = |a: (i32, i32), b: (i32, i32)| (a.0 - b.0).abs() + (a.1 - b.1).abs() == 1;
+    // The center is the block touching all three others.
+    let Some(&center) = blocks
+        .iter()
+        .find(|&&c| blocks.iter().filter(|&&b| adjacent(b, c)).count() == 3)
+    else {
+        return TSpin::None;
+    };
+    // The nub is the arm without a block on the opposite side.
+    let Some(&nub) = blocks.iter().find(|&&b| {
+        adjacent(b, center) && !blocks.contains(&(2 * center.0 - b.0, 2 * center.1 - b.1))
+    }) else {
+        return TSpin::None;
+    };
+
+    let (dx, dy) = (nub.0 - center.0, nub.1 - center.1);
+    let filled = |(x, y): (i32, i32)| occupied(center.0 + x, center.1 + y);
+    let front = [(dx + dy, dy + dx), (dx - dy, dy - dx)];
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+    if corners.into_iter().filter(|&c| filled(c)).count() < 3 {
+        TSpin::None
+    } else if front.into_iter().all(filled) || (kick.0.abs() == 1 && kick.1.abs() == 2) {
+        TSpin::Full
+    } else {
+        TSpin::Mini
+    }
+}
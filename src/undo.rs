@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use rand_chacha::ChaCha12Rng;
+
+use crate::{
+    Board, Command, Delay, Frame, Game, GameOverReason, InputBuffer, Piece, Randomizer, Step,
+    Tetromino, Turn,
+};
+
+/// A frozen copy of a game's state, taken with [`Game::snapshot`] and put
+/// back with [`Game::restore`]. The config and pending events are not part
+/// of it, so restore it into the game it came from.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub(crate) board: Board,
+    pub(crate) current: Piece,
+    pub(crate) queue: VecDeque<Tetromino>,
+    pub(crate) held: Option<Tetromino>,
+    pub(crate) hold_used: bool,
+    pub(crate) initial_rotation: Option<Turn>,
+    pub(crate) initial_hold: bool,
+    pub(crate) pieces_spawned: u32,
+    pub(crate) lock_timer: Option<Duration>,
+    pub(crate) lock_resets: u32,
+    pub(crate) lowest_y: i32,
+    pub(crate) fall_progress: f64,
+    pub(crate) last_kick: Option<(i32, i32)>,
+    pub(crate) delay: Option<Delay>,
+    pub(crate) randomizer: Box<dyn Randomizer>,
+    pub(crate) seed: u64,
+    pub(crate) rng: ChaCha12Rng,
+    pub(crate) score: u32,
+    pub(crate) lines: u32,
+    pub(crate) combo: Option<u32>,
+    pub(crate) back_to_back: bool,
+    pub(crate) game_over: Option<GameOverReason>,
+}
+
+/// Undo and redo for whole placements.
+///
+/// Route commands and time through [`apply`](Self::apply) and
+/// [`update`](Self::update), or frames through [`step`](Self::step), so the
+/// stack sees every lock. Undo goes back to
+/// the moment the last placed piece spawned; moves made with the current
+/// piece are dropped.
+#[derive(Debug, Clone)]
+pub struct UndoStack {
+    // State when the current piece spawned.
+    start: Snapshot,
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    limit: usize,
+}
+
+impl UndoStack {
+    /// Keeps up to 100 placements.
+    pub fn new(game: &Game) -> Self {
+        Self::with_limit(game, 100)
+    }
+
+    /// Keeps up to `limit` placements, dropping the oldest ones.
+    pub fn with_limit(game: &Game, limit: usize) -> Self {
+        UndoStack {
+            start: game.snapshot(),
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Runs `command` on `game`, recording the placement if a piece locks.
+    pub fn apply(&mut self, game: &mut Game, command: Command) -> Option<Step> {
+        let step = game.apply(command);
+        self.record(game, matches!(step, Some(Step::Locked(_))));
+        step
+    }
+
+    /// Advances `game` by `dt`, recording the placement if a piece locks.
+    pub fn update(&mut self, game: &mut Game, dt: Duration) -> Option<Step> {
+        let step = game.update(dt);
+        self.record(game, matches!(step, Some(Step::Locked(_))));
+        step
+    }
+
+    /// Runs one frame of `input` on `game`, recording the placement if a
+    /// piece locks. Several locks in one frame undo together.
+    pub fn step(&mut self, game: &mut Game, input: &mut InputBuffer) -> Frame {
+        let frame = input.step(game);
+        self.record(game, frame.locks().next().is_some());
+        frame
+    }
+
+    /// Takes back the last placement. Returns false if there is none.
+    pub fn undo(&mut self, game: &mut Game) -> bool {
+        let Some(previous) = self.undo.pop_back() else {
+            return false;
+        };
+        self.redo.push(std::mem::replace(&mut self.start, previous));
+        game.restore(&self.start);
+        true
+    }
+
+    /// Puts back the last placement taken back by `undo`. Returns false if
+    /// there is none, or a new piece has locked since.
+    pub fn redo(&mut self, game: &mut Game) -> bool {
+        let Some(next) = self.redo.pop() else {
+            return false;
+        };
+        self.undo
+            .push_back(std::mem::replace(&mut self.start, next));
+        game.restore(&self.start);
+        true
+    }
+
+    fn record(&mut self, game: &Game, locked: bool) {
+        if locked {
+            self.undo
+                .push_back(std::mem::replace(&mut self.start, game.snapshot()));
+            if self.undo.len() > self.limit {
+                self.undo.pop_front();
+            }
+            self.redo.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo_placements() {
+        let mut g = Game::with_seed(5);
+        let mut history = UndoStack::new(&g);
+        let first = g.current_piece();
+        let queue: Vec<_> = g.next_queue().collect();
+
+        history.apply(&mut g, Command::Left);
+        history.apply(&mut g, Command::HardDrop);
+        let placed = g.full_board().to_vec();
+        let score = g.score();
+        history.apply(&mut g, Command::HardDrop);
+        assert!(!history.can_redo());
+
+        assert!(history.undo(&mut g));
+        assert_eq!(g.full_board(), placed);
+        assert_eq!(g.score(), score);
+        assert!(history.undo(&mut g));
+        assert_eq!(g.current_piece(), first);
+        assert_eq!(g.next_queue().collect::<Vec<_>>(), queue);
+        assert_eq!(g.score(), 0);
+        assert!(!history.undo(&mut g));
+
+        assert!(history.redo(&mut g));
+        assert_eq!(g.full_board(), placed);
+        // A new placement drops what was left to redo.
+        history.apply(&mut g, Command::Right);
+        history.apply(&mut g, Command::HardDrop);
+        assert!(!history.redo(&mut g));
+    }
+
+    #[test]
+    fn undo_through_an_input_buffer() {
+        let mut g = Game::with_seed(5);
+        let mut history = UndoStack::new(&g);
+        let mut input = InputBuffer::new();
+        let first = g.current_piece();
+        input.push(3, Command::HardDrop);
+        for _ in 0..5 {
+            history.step(&mut g, &mut input);
+        }
+        assert!(history.undo(&mut g));
+        assert_eq!(g.current_piece(), first);
+        assert!(!history.undo(&mut g));
+    }
+
+    #[test]
+    fn limit_drops_the_oldest_placement() {
+        let mut g = Game::with_seed(5);
+        let mut history = UndoStack::with_limit(&g, 2);
+        for _ in 0..3 {
+            history.apply(&mut g, Command::HardDrop);
+        }
+        assert!(history.undo(&mut g));
+        assert!(history.undo(&mut g));
+        assert!(!history.undo(&mut g));
+        assert_eq!(g.full_board().iter().filter(|&&c| c != 0).count(), 4);
+    }
+}